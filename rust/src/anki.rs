@@ -0,0 +1,291 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Map, Value};
+use crate::reading::Segment;
+
+// AnkiConnect's current API version
+const API_VERSION: u32 = 6;
+// Every mined note carries this tag, so cards from the reader are easy to find
+const NOTE_TAG: &str = "vets";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AnkiConfig {
+    // AnkiConnect listens on 127.0.0.1:8765 unless its webBindAddress is changed
+    pub url: String,
+    pub deck: String,
+    pub note_type: String,
+    pub fields: FieldMapping,
+}
+
+impl Default for AnkiConfig {
+    fn default() -> Self {
+        AnkiConfig {
+            url: "http://127.0.0.1:8765".to_string(),
+            deck: "Default".to_string(),
+            note_type: "Basic".to_string(),
+            fields: FieldMapping::default(),
+        }
+    }
+}
+
+// Name of the note type field each part of a card goes into, empty to leave it out
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FieldMapping {
+    pub sentence: String,
+    pub reading: String,
+    pub translation: String,
+    pub screenshot: String,
+    #[serde(default)]
+    pub word: String,
+    #[serde(default)]
+    pub meaning: String,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        FieldMapping {
+            sentence: "Front".to_string(),
+            reading: String::new(),
+            translation: "Back".to_string(),
+            screenshot: "Back".to_string(),
+            word: String::new(),
+            meaning: String::new(),
+        }
+    }
+}
+
+// The word looked up in the popup, when a card is mined from there
+#[derive(Clone)]
+pub struct MinedWord {
+    pub word: String,
+    pub reading: String,
+    pub meaning: String,
+}
+
+pub struct MinedNote {
+    pub sentence: String,
+    // In Anki's furigana syntax, 漢字[かんじ]
+    pub reading: String,
+    pub translation: String,
+    pub word: Option<MinedWord>,
+    // PNG of the capture the packet was read from
+    pub screenshot: Option<Vec<u8>>,
+    pub screenshot_name: String,
+}
+
+// ________________________________________
+// Readings as Anki's {{furigana:}} filter
+// expects them: a space before each kanji
+// run and its reading in brackets after it
+// ----------------------------------------
+pub fn anki_furigana(segments: &[Segment]) -> String {
+    let mut furigana_text = String::new();
+    for segment in segments {
+        match &segment.reading {
+            Some(segment_reading) => {
+                if !furigana_text.is_empty() {
+                    furigana_text.push(' ');
+                }
+                furigana_text.push_str(&format!("{}[{}]", segment.text, segment_reading));
+            },
+            None => furigana_text.push_str(&segment.text),
+        }
+    }
+    furigana_text
+}
+
+// ________________________________________
+// The addNote request. Several parts mapped
+// to the same field are joined with a line
+// break, the screenshot is appended by
+// AnkiConnect itself
+// ----------------------------------------
+pub fn add_note_request(config: &AnkiConfig, note: &MinedNote) -> Value {
+    let mut fields: Map<String, Value> = Map::new();
+    let mut add_field = |field: &str, value: &str| {
+        if field.trim().is_empty() || value.is_empty() {
+            return;
+        }
+        let value = escape_html(value);
+        match fields.get_mut(field.trim()) {
+            Some(Value::String(existing)) => existing.push_str(&format!("<br>{}", value)),
+            _ => {
+                fields.insert(field.trim().to_string(), Value::String(value));
+            },
+        }
+    };
+    add_field(&config.fields.sentence, &note.sentence);
+    add_field(&config.fields.reading, &note.reading);
+    add_field(&config.fields.translation, &note.translation);
+    if let Some(mined_word) = &note.word {
+        let word = if mined_word.reading.is_empty() || mined_word.reading == mined_word.word {
+            mined_word.word.clone()
+        } else {
+            format!("{}[{}]", mined_word.word, mined_word.reading)
+        };
+        add_field(&config.fields.word, &word);
+        add_field(&config.fields.meaning, &mined_word.meaning);
+    }
+    let mut anki_note = json!({
+        "deckName": config.deck,
+        "modelName": config.note_type,
+        "fields": fields,
+        "options": {
+            "allowDuplicate": false,
+            "duplicateScope": "deck"
+        },
+        "tags": [NOTE_TAG]
+    });
+    if let (Some(png_bytes), false) = (&note.screenshot, config.fields.screenshot.trim().is_empty()) {
+        anki_note["picture"] = json!([{
+            "data": STANDARD.encode(png_bytes),
+            "filename": note.screenshot_name,
+            "fields": [config.fields.screenshot.trim()]
+        }]);
+    }
+    json!({
+        "action": "addNote",
+        "version": API_VERSION,
+        "params": {
+            "note": anki_note
+        }
+    })
+}
+
+// Returns the id of the new note
+pub async fn add_note(config: &AnkiConfig, note: &MinedNote) -> Result<u64, String> {
+    let client = reqwest::Client::new();
+    let response = client.post(&config.url)
+        .json(&add_note_request(config, note))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach AnkiConnect at {}. Is Anki running with the AnkiConnect add-on? {}", config.url, e))?
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("AnkiConnect sent an unexpected response: {}", e))?;
+    // AnkiConnect answers {"result": ..., "error": ...} with one of the two null
+    if let Some(error) = response["error"].as_str() {
+        return Err(format!("AnkiConnect refused the note: {}", error));
+    }
+    response["result"].as_u64().ok_or_else(|| format!("AnkiConnect sent an unexpected response: {}", response))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Answers a single request with the given AnkiConnect body and hands back the JSON it was sent
+    fn ankiconnect(answer: &'static str) -> (String, thread::JoinHandle<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", answer.len(), answer);
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            serde_json::from_slice(&body).unwrap()
+        });
+        (url, handle)
+    }
+
+    fn config(url: String) -> AnkiConfig {
+        AnkiConfig {
+            url,
+            deck: "Japanese::Mining".to_string(),
+            note_type: "Sentence".to_string(),
+            fields: FieldMapping {
+                sentence: "Sentence".to_string(),
+                reading: "Reading".to_string(),
+                translation: "Back".to_string(),
+                screenshot: "Back".to_string(),
+                word: "Word".to_string(),
+                meaning: "Back".to_string(),
+            },
+        }
+    }
+
+    fn note() -> MinedNote {
+        MinedNote {
+            sentence: "猫を食べた".to_string(),
+            reading: " 猫[ねこ]を 食[た]べた".to_string(),
+            translation: "I ate a cat".to_string(),
+            word: Some(MinedWord { word: "食べる".to_string(), reading: "たべる".to_string(), meaning: "to eat".to_string() }),
+            screenshot: Some(vec![0x89, b'P', b'N', b'G']),
+            screenshot_name: "vets_1.png".to_string(),
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Runtime::new().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn add_note_sends_mapped_fields() {
+        let (url, ankiconnect) = ankiconnect(r#"{"result": 1496198395707, "error": null}"#);
+        let note_id = block_on(add_note(&config(url), &note()));
+        assert_eq!(note_id, Ok(1496198395707));
+
+        let request = ankiconnect.join().unwrap();
+        assert_eq!(request["action"], "addNote");
+        assert_eq!(request["version"], 6);
+        let anki_note = &request["params"]["note"];
+        assert_eq!(anki_note["deckName"], "Japanese::Mining");
+        assert_eq!(anki_note["modelName"], "Sentence");
+        assert_eq!(anki_note["tags"], json!(["vets"]));
+        assert_eq!(anki_note["fields"], json!({
+            "Sentence": "猫を食べた",
+            "Reading": " 猫[ねこ]を 食[た]べた",
+            "Back": "I ate a cat<br>to eat",
+            "Word": "食べる[たべる]"
+        }));
+        assert_eq!(anki_note["picture"], json!([{
+            "data": "iVBORw==",
+            "filename": "vets_1.png",
+            "fields": ["Back"]
+        }]));
+    }
+
+    #[test]
+    fn add_note_leaves_out_unmapped_parts() {
+        let (url, ankiconnect) = ankiconnect(r#"{"result": 1, "error": null}"#);
+        let mut anki_config = config(url);
+        anki_config.fields.reading = String::new();
+        anki_config.fields.screenshot = " ".to_string();
+        block_on(add_note(&anki_config, &note())).unwrap();
+
+        let anki_note = &ankiconnect.join().unwrap()["params"]["note"];
+        assert!(anki_note["fields"].get("Reading").is_none());
+        assert!(anki_note.get("picture").is_none());
+    }
+
+    #[test]
+    fn add_note_reports_ankiconnect_errors() {
+        let (url, ankiconnect) = ankiconnect(r#"{"result": null, "error": "cannot create note because it is a duplicate"}"#);
+        let result = block_on(add_note(&config(url), &note()));
+        ankiconnect.join().unwrap();
+        assert_eq!(result, Err("AnkiConnect refused the note: cannot create note because it is a duplicate".to_string()));
+    }
+}
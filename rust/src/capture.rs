@@ -0,0 +1,281 @@
+use std::io::Cursor;
+use std::sync::Mutex;
+use xcap::Window;
+use image::{RgbaImage, ImageOutputFormat, GenericImageView, DynamicImage};
+use image::imageops::FilterType;
+
+#[cfg(target_os = "linux")]
+use crate::wayland;
+
+// Crops are compared as grayscale thumbnails of this size. A dialogue box of
+// 1280x240 leaves each glyph of a 32px font about 6x8 thumbnail pixels
+const THUMBNAIL_WIDTH: u32 = 256;
+const THUMBNAIL_HEIGHT: u32 = 64;
+// Brightness change of a thumbnail pixel still counted as the same pixel,
+// so compression noise and slight colour shifts are ignored
+const PIXEL_TOLERANCE: u8 = 24;
+// Two crops with this many changed thumbnail pixels or fewer are treated as the
+// same text: enough for a blinking "next" arrow, well under one changed glyph pair
+const CHANGED_PIXEL_LIMIT: usize = 48;
+
+// Target window entry that captures through the xdg-desktop-portal instead of xcap
+pub const WAYLAND_PORTAL: &str = "Wayland Portal";
+
+// The ScreenCast session shared by every capture of the Wayland Portal entry,
+// kept open until the target window changes or the app exits
+#[derive(Default)]
+pub struct Portal {
+    pub restore_token: Option<String>,
+    #[cfg(target_os = "linux")]
+    capture: Option<wayland::PortalCapture>,
+}
+
+impl Portal {
+    pub fn close(&mut self) {
+        #[cfg(target_os = "linux")]
+        {
+            self.capture = None;
+        }
+    }
+}
+
+pub fn list_window_titles() -> Vec<String> {
+    let mut titles: Vec<String> = Window::all().unwrap_or_default().into_iter()
+        .filter(|w| !w.is_minimized())
+        .map(|w| w.title().to_string())
+        .collect();
+    #[cfg(target_os = "linux")]
+    if wayland::is_wayland_session() {
+        titles.insert(0, WAYLAND_PORTAL.to_string());
+    }
+    titles
+}
+
+// ________________________________________
+// Capture a window and crop it to the
+// reading area
+// ----------------------------------------
+pub fn capture_window(window_title: &str, portal: &Mutex<Portal>, up: u32, down: u32, left: u32, right: u32) -> Result<RgbaImage, String> {
+    let image = if window_title == WAYLAND_PORTAL {
+        capture_portal(portal)?
+    } else {
+        let windows = Window::all().map_err(|e| format!("Window listing failure: {}", e))?;
+        let window = windows.into_iter().find(|w| w.title() == window_title && !w.is_minimized());
+        if let Some(window) = window {
+            window.capture_image().map_err(|e| format!("Window capturing failure: {}", e))?
+        } else {
+            return Err("Target window not found: make sure to refresh window list!".to_string());
+        }
+    };
+    let new_width = image.width().saturating_sub(left + right);
+    let new_height = image.height().saturating_sub(up + down);
+    Ok(image.view(left, up, new_width, new_height).to_image())
+}
+
+#[cfg(target_os = "linux")]
+fn capture_portal(portal: &Mutex<Portal>) -> Result<RgbaImage, String> {
+    let mut portal = portal.lock().unwrap();
+    // A stream that stopped, like when the shared window was closed, is asked for again
+    if !portal.capture.as_ref().is_some_and(|capture| capture.is_open()) {
+        portal.capture = None;
+        let capture = wayland::PortalCapture::open(portal.restore_token.clone())?;
+        // Restore tokens are single-use: the portal hands out a new one every session
+        portal.restore_token = capture.restore_token();
+        portal.capture = Some(capture);
+    }
+    portal.capture.as_ref().unwrap().frame()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn capture_portal(_portal: &Mutex<Portal>) -> Result<RgbaImage, String> {
+    Err("Wayland capture is only available on Linux!".to_string())
+}
+
+// ________________________________________
+// Load an image from disk or from memory,
+// for screenshots that were not captured
+// from a window
+// ----------------------------------------
+pub fn load_image_file(path: &str) -> Result<RgbaImage, String> {
+    match image::open(path) {
+        Ok(image) => Ok(image.to_rgba8()),
+        Err(e) => Err(format!("Failed to open image {}: {}", path, e)),
+    }
+}
+
+pub fn decode_image(bytes: &[u8]) -> Result<RgbaImage, String> {
+    match image::load_from_memory(bytes) {
+        Ok(image) => Ok(image.to_rgba8()),
+        Err(e) => Err(format!("Failed to decode image: {}", e)),
+    }
+}
+
+pub fn encode_png(image: RgbaImage) -> Cursor<Vec<u8>> {
+    let mut png_buffer = Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(image).write_to(&mut png_buffer, ImageOutputFormat::Png).unwrap();
+    png_buffer
+}
+
+// ________________________________________
+// Fingerprint of a crop: its downscaled
+// grayscale pixels, compared pixel by
+// pixel against the next one
+// ----------------------------------------
+pub fn fingerprint(image: &RgbaImage) -> Vec<u8> {
+    DynamicImage::ImageRgba8(image.clone()).resize_exact(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, FilterType::Triangle).to_luma8().into_raw()
+}
+
+fn is_same_crop(a: &[u8], b: &[u8]) -> bool {
+    let changed_pixels = a.iter().zip(b.iter()).filter(|(a, b)| a.abs_diff(**b) > PIXEL_TOLERANCE).count();
+    a.len() == b.len() && changed_pixels <= CHANGED_PIXEL_LIMIT
+}
+
+// ============================================================
+// -- AutoCapture --
+// Decides when a sampled reading area holds new, settled text
+// ============================================================
+pub struct AutoCapture {
+    pub is_sampling: bool,
+    pub time_accumulator: f32,
+    last_sample: Option<Vec<u8>>,
+    last_read: Option<Vec<u8>>,
+}
+
+impl Default for AutoCapture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AutoCapture {
+    pub fn new() -> Self {
+        AutoCapture {
+            is_sampling: false,
+            time_accumulator: 0.0,
+            last_sample: None,
+            last_read: None,
+        }
+    }
+
+    // Returns true when the sample matches the previous one (text has stabilized)
+    // and differs from the last crop that was sent to OCR
+    pub fn observe(&mut self, fingerprint: &[u8]) -> bool {
+        let is_stable = match &self.last_sample {
+            Some(last_sample) => is_same_crop(last_sample, fingerprint),
+            None => false,
+        };
+        self.last_sample = Some(fingerprint.to_vec());
+        if !is_stable {
+            return false;
+        }
+        let is_new = match &self.last_read {
+            Some(last_read) => !is_same_crop(last_read, fingerprint),
+            None => true,
+        };
+        if is_new {
+            self.mark_read(fingerprint);
+        }
+        is_new
+    }
+
+    // Manual captures count as read too, so the same text is not captured twice
+    pub fn mark_read(&mut self, fingerprint: &[u8]) {
+        self.last_sample = Some(fingerprint.to_vec());
+        self.last_read = Some(fingerprint.to_vec());
+    }
+
+    pub fn reset(&mut self) {
+        self.is_sampling = false;
+        self.time_accumulator = 0.0;
+        self.last_sample = None;
+        self.last_read = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // A dark 1280x240 dialogue box with lines of 32px "glyphs", each a 4x4
+    // grid of strokes picked by the bits of its code
+    fn dialogue_box(lines: &[&[u16]]) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(1280, 240, Rgba([20, 24, 40, 255]));
+        for (row, line) in lines.iter().enumerate() {
+            for (column, code) in line.iter().enumerate() {
+                for bit in 0..16 {
+                    if code & (1 << bit) == 0 {
+                        continue;
+                    }
+                    let x0 = 40 + column as u32 * 36 + (bit % 4) * 8;
+                    let y0 = 30 + row as u32 * 60 + (bit / 4) * 8;
+                    for y in y0..y0 + 8 {
+                        for x in x0..x0 + 8 {
+                            image.put_pixel(x, y, Rgba([235, 235, 235, 255]));
+                        }
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    const LINE_1: &[u16] = &[0x9f21, 0x4a5c, 0x03f0, 0xc3c3, 0x5a5a, 0x0ff0, 0x8421, 0x1248, 0xf00f, 0x6996];
+    const LINE_2: &[u16] = &[0x3c3c, 0xa5a5, 0x1e78, 0x7e81, 0x0f0f, 0x9669];
+
+    #[test]
+    fn same_text_is_the_same_crop() {
+        let image = dialogue_box(&[LINE_1, LINE_2]);
+        assert!(is_same_crop(&fingerprint(&image), &fingerprint(&image.clone())));
+    }
+
+    #[test]
+    fn noise_is_the_same_crop() {
+        let image = dialogue_box(&[LINE_1, LINE_2]);
+        let mut noisy_image = image.clone();
+        for (x, y, pixel) in noisy_image.enumerate_pixels_mut() {
+            let noise = ((x * 7 + y * 13) % 11) as u8;
+            pixel.0 = [pixel[0].saturating_add(noise), pixel[1].saturating_sub(noise), pixel[2].saturating_add(noise / 2), 255];
+        }
+        assert!(is_same_crop(&fingerprint(&image), &fingerprint(&noisy_image)));
+    }
+
+    #[test]
+    fn blinking_arrow_is_the_same_crop() {
+        let image = dialogue_box(&[LINE_1, LINE_2]);
+        let mut arrow_image = image.clone();
+        for y in 200..216 {
+            for x in 1240..1256 {
+                arrow_image.put_pixel(x, y, Rgba([235, 235, 235, 255]));
+            }
+        }
+        assert!(is_same_crop(&fingerprint(&image), &fingerprint(&arrow_image)));
+    }
+
+    #[test]
+    fn new_line_is_a_new_crop() {
+        let image = dialogue_box(&[LINE_1, LINE_2]);
+        let new_image = dialogue_box(&[LINE_1, &[0xc3a5, 0x5a3c, 0x81e7, 0x18f0, 0xf0a1, 0x2d4b]]);
+        assert!(!is_same_crop(&fingerprint(&image), &fingerprint(&new_image)));
+    }
+
+    #[test]
+    fn two_changed_glyphs_are_a_new_crop() {
+        let image = dialogue_box(&[&[0x9f21, 0x4a5c]]);
+        let new_image = dialogue_box(&[&[0x4a5c, 0x9f21]]);
+        assert!(!is_same_crop(&fingerprint(&image), &fingerprint(&new_image)));
+    }
+
+    #[test]
+    fn auto_capture_waits_for_settled_new_text() {
+        let mut auto_capture = AutoCapture::new();
+        let first = fingerprint(&dialogue_box(&[LINE_1]));
+        let second = fingerprint(&dialogue_box(&[LINE_1, LINE_2]));
+        assert!(!auto_capture.observe(&first));
+        assert!(auto_capture.observe(&first));
+        assert!(!auto_capture.observe(&first));
+        // Text still appearing, then settled
+        assert!(!auto_capture.observe(&second));
+        assert!(auto_capture.observe(&second));
+    }
+}
@@ -0,0 +1,236 @@
+// Word types a form can take, so rules only chain where the grammar allows
+pub const V1: u32 = 1;
+pub const V5: u32 = 2;
+pub const VK: u32 = 4;
+pub const VS: u32 = 8;
+pub const ADJ_I: u32 = 16;
+pub const MASU: u32 = 32;
+pub const TE: u32 = 64;
+pub const ANY: u32 = V1 | V5 | VK | VS | ADJ_I | MASU | TE;
+
+// Godan endings by row: u, i, a, e, o, and the past/te form
+const GODAN: [(&str, &str, &str, &str, &str, &str); 9] = [
+    ("う", "い", "わ", "え", "お", "っ"),
+    ("く", "き", "か", "け", "こ", "い"),
+    ("ぐ", "ぎ", "が", "げ", "ご", "い"),
+    ("す", "し", "さ", "せ", "そ", "し"),
+    ("つ", "ち", "た", "て", "と", "っ"),
+    ("ぬ", "に", "な", "ね", "の", "ん"),
+    ("ぶ", "び", "ば", "べ", "ぼ", "ん"),
+    ("む", "み", "ま", "め", "も", "ん"),
+    ("る", "り", "ら", "れ", "ろ", "っ"),
+];
+
+struct Rule {
+    from: String,
+    to: String,
+    // Type the inflected form must have to take this rule
+    type_in: u32,
+    // Type of the form the rule gives back
+    type_out: u32,
+    reason: &'static str,
+}
+
+// A form the text might have come from, with the steps taken to get there
+#[derive(Clone)]
+pub struct Deinflection {
+    pub text: String,
+    pub word_type: u32,
+    pub reasons: Vec<&'static str>,
+}
+
+// ________________________________________
+// Undo conjugations step by step, so that
+// 食べさせられなかった is traced back to
+// 食べる. Every step is kept, the dictionary
+// decides which of them are real words
+// ----------------------------------------
+pub fn deinflect(text: &str) -> Vec<Deinflection> {
+    let rules = rules();
+    let mut results = vec![Deinflection { text: text.to_string(), word_type: ANY, reasons: Vec::new() }];
+    let mut i = 0;
+    while i < results.len() {
+        let current = results[i].clone();
+        for rule in &rules {
+            if current.word_type & rule.type_in == 0 || !current.text.ends_with(&rule.from) {
+                continue;
+            }
+            let stem = &current.text[..current.text.len() - rule.from.len()];
+            let text = format!("{}{}", stem, rule.to);
+            if text.is_empty() || results.iter().any(|result| result.text == text && result.word_type == rule.type_out) {
+                continue;
+            }
+            let mut reasons = current.reasons.clone();
+            reasons.push(rule.reason);
+            results.push(Deinflection { text, word_type: rule.type_out, reasons });
+        }
+        i += 1;
+    }
+    results
+}
+
+// Whether a JMdict part of speech fits the type a deinflection ended on
+pub fn matches_pos(word_type: u32, pos: &[String]) -> bool {
+    if word_type == ANY {
+        return true;
+    }
+    pos.iter().any(|pos| {
+        (word_type & V1 != 0 && pos.starts_with("v1"))
+            || (word_type & V5 != 0 && pos.starts_with("v5"))
+            || (word_type & VK != 0 && pos == "vk")
+            || (word_type & VS != 0 && pos.starts_with("vs"))
+            || (word_type & ADJ_I != 0 && pos.starts_with("adj-i"))
+    })
+}
+
+fn rules() -> Vec<Rule> {
+    let mut rules = Vec::new();
+    let mut add = |from: &str, to: &str, type_in: u32, type_out: u32, reason: &'static str| {
+        rules.push(Rule { from: from.to_string(), to: to.to_string(), type_in, type_out, reason });
+    };
+    // -- STEM FORMS --
+    // Suffixes that attach to the stem of every verb type the same way
+    let verb_suffixes: [(&str, u32, &'static str); 10] = [
+        ("ます", MASU, "polite"),
+        ("たい", ADJ_I, "want"),
+        ("ながら", ANY, "while"),
+        ("なさい", ANY, "imperative"),
+        ("そう", ANY, "seems"),
+        ("た", ANY, "past"),
+        ("て", TE, "te"),
+        ("ない", ADJ_I, "negative"),
+        ("ず", ANY, "negative"),
+        ("よう", ANY, "volitional"),
+    ];
+    for (suffix, type_in, reason) in verb_suffixes {
+        match suffix {
+            // Godan verbs take these on other rows than the i-stem
+            "た" | "て" => {
+                let voiced = if suffix == "た" { "だ" } else { "で" };
+                for (u, _, _, _, _, past) in GODAN {
+                    match u {
+                        "ぐ" | "ぬ" | "ぶ" | "む" => add(&format!("{}{}", past, voiced), u, type_in, V5, reason),
+                        "す" => add(&format!("し{}", suffix), u, type_in, V5, reason),
+                        _ => add(&format!("{}{}", past, suffix), u, type_in, V5, reason),
+                    }
+                }
+                // 行く is the one godan verb in く with a っ past
+                add(&format!("いっ{}", suffix), "いく", type_in, V5, reason);
+                add(&format!("行っ{}", suffix), "行く", type_in, V5, reason);
+            },
+            "ない" | "ず" => {
+                for (u, _, a, _, _, _) in GODAN {
+                    add(&format!("{}{}", a, suffix), u, type_in, V5, reason);
+                }
+            },
+            "よう" => {
+                for (u, _, _, _, o, _) in GODAN {
+                    add(&format!("{}う", o), u, type_in, V5, reason);
+                }
+            },
+            _ => {
+                for (u, i, _, _, _, _) in GODAN {
+                    add(&format!("{}{}", i, suffix), u, type_in, V5, reason);
+                }
+            },
+        }
+        add(suffix, "る", type_in, V1, reason);
+        let (kuru_stem, suru_stem) = match suffix {
+            "ない" | "ず" => ("こ", "し"),
+            "よう" => ("こ", "し"),
+            _ => ("き", "し"),
+        };
+        add(&format!("{}{}", kuru_stem, suffix), "くる", type_in, VK, reason);
+        add(&format!("来{}", suffix), "来る", type_in, VK, reason);
+        add(&format!("{}{}", suru_stem, suffix), "する", type_in, VS, reason);
+    }
+    add("せず", "する", ANY, VS, "negative");
+    // -- POLITE --
+    add("ました", "ます", ANY, MASU, "past");
+    add("ません", "ます", ANY, MASU, "negative");
+    add("ませんでした", "ます", ANY, MASU, "past negative");
+    add("ましょう", "ます", ANY, MASU, "volitional");
+    add("まして", "ます", ANY, MASU, "te");
+    // -- CONDITIONAL AND IMPERATIVE --
+    for (u, _, _, e, _, _) in GODAN {
+        add(&format!("{}ば", e), u, ANY, V5, "conditional");
+        add(e, u, ANY, V5, "imperative");
+    }
+    add("れば", "る", ANY, V1, "conditional");
+    add("ろ", "る", ANY, V1, "imperative");
+    add("くれば", "くる", ANY, VK, "conditional");
+    add("すれば", "する", ANY, VS, "conditional");
+    add("しろ", "する", ANY, VS, "imperative");
+    add("こい", "くる", ANY, VK, "imperative");
+    // -- POTENTIAL, PASSIVE AND CAUSATIVE --
+    // All of them are ichidan verbs themselves
+    for (u, _, a, e, _, _) in GODAN {
+        add(&format!("{}る", e), u, V1, V5, "potential");
+        add(&format!("{}れる", a), u, V1, V5, "passive");
+        add(&format!("{}せる", a), u, V1, V5, "causative");
+    }
+    add("られる", "る", V1, V1, "passive");
+    add("させる", "る", V1, V1, "causative");
+    add("こられる", "くる", V1, VK, "passive");
+    add("こさせる", "くる", V1, VK, "causative");
+    add("される", "する", V1, VS, "passive");
+    add("させる", "する", V1, VS, "causative");
+    add("できる", "する", V1, VS, "potential");
+    // -- PROGRESSIVE AND COMPLETION --
+    add("ている", "て", V1, TE, "progressive");
+    add("てる", "て", V1, TE, "progressive");
+    add("でいる", "で", V1, TE, "progressive");
+    add("でる", "で", V1, TE, "progressive");
+    add("てしまう", "て", V5, TE, "completion");
+    add("ちゃう", "て", V5, TE, "completion");
+    add("じゃう", "で", V5, TE, "completion");
+    // -- I-ADJECTIVES --
+    add("かった", "い", ANY, ADJ_I, "past");
+    add("くない", "い", ANY, ADJ_I, "negative");
+    add("くて", "い", ANY, ADJ_I, "te");
+    add("く", "い", ANY, ADJ_I, "adverbial");
+    add("ければ", "い", ANY, ADJ_I, "conditional");
+    add("さ", "い", ANY, ADJ_I, "noun");
+    add("すぎる", "い", V1, ADJ_I, "too much");
+    add("そう", "い", ANY, ADJ_I, "seems");
+    // Negatives and the want form conjugate like adjectives
+    add("なかった", "ない", ANY, ADJ_I, "past");
+    add("なくて", "ない", ANY, ADJ_I, "te");
+    add("なければ", "ない", ANY, ADJ_I, "conditional");
+    add("たかった", "たい", ANY, ADJ_I, "past");
+    add("たくない", "たい", ANY, ADJ_I, "negative");
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_form(text: &str, form: &str, word_type: u32) -> bool {
+        deinflect(text).iter().any(|result| result.text == form && result.word_type == word_type)
+    }
+
+    #[test]
+    fn chained_conjugations() {
+        assert!(has_form("食べさせられなかった", "食べる", V1));
+        let result = deinflect("食べさせられなかった").into_iter().find(|result| result.text == "食べる").unwrap();
+        assert_eq!(result.reasons, vec!["past", "negative", "passive", "causative"]);
+    }
+
+    #[test]
+    fn irregular_verbs() {
+        assert!(has_form("行った", "行く", V5));
+        assert!(has_form("いって", "いく", V5));
+        assert!(has_form("しなかった", "する", VS));
+        assert!(has_form("来ない", "来る", VK));
+    }
+
+    #[test]
+    fn godan_rows() {
+        assert!(has_form("書いた", "書く", V5));
+        assert!(has_form("泳いで", "泳ぐ", V5));
+        assert!(has_form("読まない", "読む", V5));
+        // Only the rows a past form really comes from
+        assert!(!has_form("書いた", "書す", V5));
+    }
+}
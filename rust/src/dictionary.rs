@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use crate::deinflect::{self, Deinflection};
+use crate::reading::is_kanji;
+
+// Longest stretch of text tried as one word when nothing splits the line
+const MAX_LOOKUP_LENGTH: usize = 12;
+const MAX_ENTRIES: usize = 8;
+
+// ________________________________________
+// JMdict in the jmdict-simplified JSON
+// layout (jmdict-eng-*.json), which the
+// user saves as res://dict/jmdict-eng.json
+// ----------------------------------------
+#[derive(Deserialize)]
+struct JmdictFile {
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    words: Vec<Entry>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Entry {
+    pub id: String,
+    #[serde(default)]
+    pub kanji: Vec<Spelling>,
+    #[serde(default)]
+    pub kana: Vec<Spelling>,
+    #[serde(default)]
+    pub sense: Vec<Sense>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Spelling {
+    pub text: String,
+    #[serde(default)]
+    pub common: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Sense {
+    #[serde(default, rename = "partOfSpeech")]
+    pub part_of_speech: Vec<String>,
+    #[serde(default)]
+    pub gloss: Vec<Gloss>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Gloss {
+    pub text: String,
+}
+
+impl Entry {
+    pub fn is_common(&self) -> bool {
+        self.kanji.iter().chain(self.kana.iter()).any(|spelling| spelling.common)
+    }
+
+    fn part_of_speech(&self) -> Vec<String> {
+        self.sense.iter().flat_map(|sense| sense.part_of_speech.iter().cloned()).collect()
+    }
+}
+
+// What was found for one stretch of the line
+pub struct LookupResult {
+    // The text as it appears in the line
+    pub matched: String,
+    pub deinflection: Deinflection,
+    pub entry: Entry,
+}
+
+pub struct Jmdict {
+    entries: Vec<Entry>,
+    tags: HashMap<String, String>,
+    // Every kanji and kana spelling to the entries that have it
+    index: HashMap<String, Vec<usize>>,
+}
+
+impl Jmdict {
+    pub fn from_json(bytes: &[u8]) -> Result<Self, String> {
+        let file: JmdictFile = serde_json::from_slice(bytes).map_err(|e| format!("The JMdict file could not be read: {}", e))?;
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entry) in file.words.iter().enumerate() {
+            for spelling in entry.kanji.iter().chain(entry.kana.iter()) {
+                index.entry(spelling.text.clone()).or_default().push(i);
+            }
+        }
+        Ok(Jmdict { entries: file.words, tags: file.tags, index })
+    }
+
+    // Short part of speech codes like "v5k" spelled out
+    pub fn tag(&self, code: &str) -> String {
+        self.tags.get(code).cloned().unwrap_or_else(|| code.to_string())
+    }
+
+    // ________________________________________
+    // Look up the word at the start of text.
+    // A dictionary form from the tokenizer is
+    // tried first, then ever shorter stretches
+    // of text, each one deinflected
+    // ----------------------------------------
+    pub fn lookup(&self, text: &str, base_form: Option<&str>) -> Vec<LookupResult> {
+        let mut results: Vec<LookupResult> = Vec::new();
+        if let Some(base_form) = base_form {
+            let deinflection = Deinflection { text: base_form.to_string(), word_type: deinflect::ANY, reasons: Vec::new() };
+            self.collect(text.chars().take(base_form.chars().count()).collect(), deinflection, &mut results);
+        }
+        let chars: Vec<char> = text.chars().collect();
+        for length in (1..=chars.len().min(MAX_LOOKUP_LENGTH)).rev() {
+            let matched: String = chars[..length].iter().collect();
+            for deinflection in deinflect::deinflect(&matched) {
+                self.collect(matched.clone(), deinflection, &mut results);
+            }
+            if results.len() >= MAX_ENTRIES {
+                break;
+            }
+        }
+        results.truncate(MAX_ENTRIES);
+        results
+    }
+
+    fn collect(&self, matched: String, deinflection: Deinflection, results: &mut Vec<LookupResult>) {
+        let Some(entry_indices) = self.index.get(&deinflection.text) else {
+            return;
+        };
+        // Common words first, the way a paper dictionary would list them
+        let mut entries: Vec<&Entry> = entry_indices.iter().map(|i| &self.entries[*i]).collect();
+        entries.sort_by_key(|entry| !entry.is_common());
+        for entry in entries {
+            if results.iter().any(|result| result.entry.id == entry.id) {
+                continue;
+            }
+            if !deinflect::matches_pos(deinflection.word_type, &entry.part_of_speech()) {
+                continue;
+            }
+            results.push(LookupResult {
+                matched: matched.clone(),
+                deinflection: deinflection.clone(),
+                entry: entry.clone(),
+            });
+        }
+    }
+}
+
+// Each kanji links to its breakdown in the kanji panel
+fn link_kanji(text: &str) -> String {
+    text.chars().map(|c| if is_kanji(c) { format!("[url={}]{}[/url]", c, c) } else { c.to_string() }).collect()
+}
+
+// ________________________________________
+// BBCode for the word popup: spellings,
+// the conjugation that was undone, the
+// common flag, then senses with their part
+// of speech
+// ----------------------------------------
+pub fn format_results(jmdict: &Jmdict, results: &[LookupResult]) -> String {
+    let mut lines = Vec::new();
+    for result in results {
+        let entry = &result.entry;
+        let kanji: Vec<String> = entry.kanji.iter().map(|spelling| link_kanji(&spelling.text)).collect();
+        let kana: Vec<&str> = entry.kana.iter().map(|spelling| spelling.text.as_str()).collect();
+        let mut heading = if kanji.is_empty() {
+            format!("[font_size=22]{}[/font_size]", kana.join("、"))
+        } else {
+            format!("[font_size=22]{}[/font_size]  【{}】", kanji.join("、"), kana.join("、"))
+        };
+        if entry.is_common() {
+            heading.push_str("  [color=#d9a05b]common[/color]");
+        }
+        if !result.deinflection.reasons.is_empty() {
+            heading.push_str(&format!("\n[color=#777777]{} < {}[/color]", result.matched, result.deinflection.reasons.join(" < ")));
+        }
+        lines.push(heading);
+        let mut last_pos: Vec<String> = Vec::new();
+        for (i, sense) in entry.sense.iter().enumerate() {
+            // JMdict only repeats the part of speech when it changes
+            if !sense.part_of_speech.is_empty() && sense.part_of_speech != last_pos {
+                let pos: Vec<String> = sense.part_of_speech.iter().map(|code| jmdict.tag(code)).collect();
+                lines.push(format!("[color=#777777][i]{}[/i][/color]", pos.join(", ")));
+                last_pos = sense.part_of_speech.clone();
+            }
+            let glosses: Vec<&str> = sense.gloss.iter().map(|gloss| gloss.text.as_str()).collect();
+            lines.push(format!("{}. {}", i + 1, glosses.join("; ").replace('[', "[lb]")));
+        }
+        lines.push(String::new());
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JMDICT: &str = r#"{
+        "tags": { "v1": "Ichidan verb", "v5k-s": "Godan verb - Iku/Yuku special class" },
+        "words": [
+            { "id": "1", "kanji": [{ "text": "食べる", "common": true }], "kana": [{ "text": "たべる", "common": true }],
+              "sense": [{ "partOfSpeech": ["v1", "vt"], "gloss": [{ "text": "to eat" }] }] },
+            { "id": "2", "kanji": [{ "text": "行く", "common": true }], "kana": [{ "text": "いく", "common": true }],
+              "sense": [{ "partOfSpeech": ["v5k-s", "vi"], "gloss": [{ "text": "to go" }] }] },
+            { "id": "3", "kanji": [], "kana": [{ "text": "する", "common": true }],
+              "sense": [{ "partOfSpeech": ["vs-i"], "gloss": [{ "text": "to do" }] }] },
+            { "id": "4", "kanji": [{ "text": "食べ" }], "kana": [{ "text": "たべ" }],
+              "sense": [{ "partOfSpeech": ["n"], "gloss": [{ "text": "food" }] }] }
+        ]
+    }"#;
+
+    fn ids(results: &[LookupResult]) -> Vec<&str> {
+        results.iter().map(|result| result.entry.id.as_str()).collect()
+    }
+
+    #[test]
+    fn conjugated_words_find_their_entry() {
+        let jmdict = Jmdict::from_json(JMDICT.as_bytes()).unwrap();
+        let results = jmdict.lookup("食べさせられなかった", None);
+        assert_eq!(results[0].entry.id, "1");
+        assert_eq!(results[0].matched, "食べさせられなかった");
+        assert!(ids(&jmdict.lookup("行ったよ", None)).contains(&"2"));
+        assert_eq!(jmdict.lookup("しなかった", None)[0].entry.id, "3");
+        assert_eq!(jmdict.tag("v1"), "Ichidan verb");
+    }
+
+    #[test]
+    fn tokenizer_base_form_comes_first() {
+        let jmdict = Jmdict::from_json(JMDICT.as_bytes()).unwrap();
+        let results = jmdict.lookup("食べた", Some("食べる"));
+        assert_eq!(results[0].entry.id, "1");
+        assert!(results[0].deinflection.reasons.is_empty());
+    }
+
+    #[test]
+    fn longest_match_comes_first() {
+        let jmdict = Jmdict::from_json(JMDICT.as_bytes()).unwrap();
+        let results = jmdict.lookup("食べた", None);
+        assert_eq!(ids(&results), vec!["1", "4"]);
+        assert_eq!(results[0].deinflection.reasons, vec!["past"]);
+        // The noun is only the literal 食べ, not a conjugation of it
+        assert_eq!(results[1].matched, "食べ");
+        assert!(results[1].deinflection.reasons.is_empty());
+        assert!(jmdict.lookup("猫", None).is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::{HashMap, HashSet};
+use crate::tokenizer::Word;
+
+pub const HIGHLIGHT_OFF: i32 = 0;
+pub const HIGHLIGHT_JLPT: i32 = 1;
+pub const HIGHLIGHT_FREQUENCY: i32 = 2;
+
+pub const STYLE_COLOR: i32 = 0;
+pub const STYLE_BACKGROUND: i32 = 1;
+
+// Easiest to hardest: N5 to N1, or the most common band to the rarest
+const BAND_COLORS: [&str; 5] = ["#8fc27a", "#6fb3c9", "#c9b26f", "#d98c5b", "#d96b6b"];
+// Upper rank of each frequency band but the last
+const FREQUENCY_BANDS: [u32; 4] = [1000, 3000, 6000, 12000];
+
+pub struct HighlightConfig {
+    pub mode: i32,
+    pub style: i32,
+    // Index in the Known JLPT Level list: 0 is none, 1 is N5 up to 5 for N1
+    pub known_jlpt: i32,
+    // Words ranked this common or more are known
+    pub known_frequency: u32,
+}
+
+// ________________________________________
+// Colour for a word the learner is not
+// expected to know yet, or None. Only
+// content words are judged, by dictionary
+// form
+// ----------------------------------------
+pub fn highlight_color(word: &Word, config: &HighlightConfig, jlpt_list: Option<&HashMap<String, u32>>, frequency_list: Option<&HashMap<String, u32>>) -> Option<&'static str> {
+    if !matches!(word.pos.as_str(), "名詞" | "動詞" | "形容詞" | "副詞" | "連体詞") || word.pos_detail == "数" {
+        return None;
+    }
+    let lookup = |list: &HashMap<String, u32>| list.get(&word.base_form).or_else(|| list.get(&word.surface)).copied();
+    match config.mode {
+        HIGHLIGHT_JLPT => {
+            // Words on no JLPT list are left alone, most of them are names
+            let level = lookup(jlpt_list?)?.clamp(1, 5);
+            let known_level = 6 - config.known_jlpt;
+            if config.known_jlpt > 0 && level as i32 >= known_level {
+                return None;
+            }
+            Some(BAND_COLORS[5 - level as usize])
+        },
+        HIGHLIGHT_FREQUENCY => {
+            // Like the JLPT lists, words the list does not rank are left alone
+            let rank = lookup(frequency_list?)?;
+            if rank <= config.known_frequency {
+                return None;
+            }
+            let band = FREQUENCY_BANDS.iter().position(|upper| rank <= *upper).unwrap_or(FREQUENCY_BANDS.len());
+            Some(BAND_COLORS[band])
+        },
+        _ => None,
+    }
+}
+
+pub fn highlight(text: &str, color: &str, style: i32) -> String {
+    match style {
+        STYLE_BACKGROUND => format!("[bgcolor={}55]{}[/bgcolor]", color, text),
+        _ => format!("[color={}]{}[/color]", color, text),
+    }
+}
+
+// One word per line with its level after a tab, as 5 or N5
+pub fn parse_jlpt(bytes: &[u8]) -> Result<HashMap<String, u32>, String> {
+    let text = String::from_utf8(bytes.to_vec()).map_err(|_| "The JLPT list is not UTF-8 text".to_string())?;
+    let mut jlpt_list = HashMap::new();
+    for line in text.lines() {
+        let mut columns = line.split('\t');
+        let (Some(word), Some(level)) = (columns.next(), columns.next()) else {
+            continue;
+        };
+        if let Ok(level) = level.trim().trim_start_matches(['N', 'n']).parse::<u32>() {
+            // Words listed at several levels count at the easiest one
+            let entry = jlpt_list.entry(word.trim().to_string()).or_insert(level);
+            *entry = (*entry).max(level);
+        }
+    }
+    Ok(jlpt_list)
+}
+
+// One word per line, either with its rank after a tab or in rank order
+pub fn parse_frequency(bytes: &[u8]) -> Result<HashMap<String, u32>, String> {
+    let text = String::from_utf8(bytes.to_vec()).map_err(|_| "The frequency list is not UTF-8 text".to_string())?;
+    let mut frequency_list = HashMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let mut columns = line.split('\t');
+        let Some(word) = columns.next().map(str::trim).filter(|word| !word.is_empty()) else {
+            continue;
+        };
+        let rank = columns.next().and_then(|rank| rank.trim().parse::<u32>().ok()).unwrap_or(i as u32 + 1);
+        frequency_list.entry(word.to_string()).or_insert(rank);
+    }
+    Ok(frequency_list)
+}
+
+// ________________________________________
+// The known words list as plain text, one
+// word per line. Only the first column of
+// tab-separated lines counts and # starts a
+// comment, so word lists exported from Anki
+// import as they are
+// ----------------------------------------
+pub fn parse_known_words(text: &str) -> HashSet<String> {
+    text.lines()
+        .filter_map(|line| line.split('\t').next())
+        .map(str::trim)
+        .filter(|word| !word.is_empty() && !word.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn format_known_words(known_words: &HashSet<String>) -> String {
+    let mut sorted: Vec<&String> = known_words.iter().collect();
+    sorted.sort();
+    sorted.iter().map(|word| word.as_str()).collect::<Vec<&str>>().join("\n")
+}
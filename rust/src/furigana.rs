@@ -0,0 +1,191 @@
+use std::ops::Range;
+use serde::{Serialize, Deserialize};
+use crate::ocr::{self, Extent, OcrParagraph, OcrSymbol};
+use crate::reading;
+
+// Ruby glyphs are printed at about half the size of the text they annotate
+const RUBY_SIZE_RATIO: f32 = 0.7;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FuriganaConfig {
+    pub strip: bool,
+    pub use_as_reading: bool,
+}
+
+impl Default for FuriganaConfig {
+    fn default() -> Self {
+        FuriganaConfig {
+            strip: true,
+            use_as_reading: false,
+        }
+    }
+}
+
+// ________________________________________
+// Take furigana out of the OCR text. Vision
+// either interleaves the ruby with the
+// sentence or reads it as its own small
+// paragraph next to the line it belongs to
+// ----------------------------------------
+pub fn extract_furigana(paragraphs: &mut Vec<OcrParagraph>) {
+    // -- INTERLEAVED RUBY --
+    for paragraph in paragraphs.iter_mut() {
+        let is_vertical = paragraph.is_vertical;
+        let base_size = median_size(&paragraph.symbols, is_vertical);
+        let is_small = |symbol: &OcrSymbol| is_kana(&symbol.text) && size(symbol, is_vertical) < base_size * RUBY_SIZE_RATIO;
+        let base_extents: Vec<Extent> = paragraph.symbols.iter().filter(|symbol| !is_small(symbol)).map(|symbol| ocr::extent(&symbol.bounds)).collect();
+        // Small kana like っ or ゃ are small too, but sit inside the line
+        let (furigana, symbols): (Vec<OcrSymbol>, Vec<OcrSymbol>) = paragraph.symbols.drain(..).partition(|symbol| {
+            is_small(symbol) && is_beside_line(ocr::extent(&symbol.bounds), &base_extents, is_vertical)
+        });
+        // A paragraph made of nothing but ruby is handled below
+        if symbols.is_empty() {
+            paragraph.symbols = furigana;
+            continue;
+        }
+        paragraph.symbols = symbols;
+        paragraph.furigana.extend(furigana);
+    }
+    // -- RUBY PARAGRAPHS --
+    let mut i = 0;
+    while i < paragraphs.len() {
+        let ruby_paragraph = &paragraphs[i];
+        let is_ruby_candidate = ruby_paragraph.symbols.iter().all(|symbol| is_kana(&symbol.text));
+        let base_index = if is_ruby_candidate { find_base(paragraphs, i) } else { None };
+        match base_index {
+            Some(base_index) => {
+                let ruby_paragraph = paragraphs.remove(i);
+                let base_index = if base_index > i { base_index - 1 } else { base_index };
+                paragraphs[base_index].furigana.extend(ruby_paragraph.symbols);
+            },
+            None => i += 1,
+        }
+    }
+}
+
+fn is_beside_line(symbol_extent: Extent, base_extents: &[Extent], is_vertical: bool) -> bool {
+    let symbol_center = ocr::center(&ocr::to_polygon(symbol_extent));
+    let closest_base = base_extents.iter().min_by(|a, b| {
+        let (a_center, b_center) = (ocr::center(&ocr::to_polygon(**a)), ocr::center(&ocr::to_polygon(**b)));
+        let a_distance = (a_center.x - symbol_center.x).powi(2) + (a_center.y - symbol_center.y).powi(2);
+        let b_distance = (b_center.x - symbol_center.x).powi(2) + (b_center.y - symbol_center.y).powi(2);
+        a_distance.total_cmp(&b_distance)
+    });
+    match closest_base {
+        Some(base_extent) if is_vertical => symbol_center.x > base_extent.max_x,
+        Some(base_extent) => symbol_center.y < base_extent.min_y,
+        None => false,
+    }
+}
+
+// The paragraph whose lines a small kana paragraph sits on top of
+// (or to the right of, for vertical text)
+fn find_base(paragraphs: &[OcrParagraph], ruby_index: usize) -> Option<usize> {
+    let ruby_paragraph = &paragraphs[ruby_index];
+    let ruby_extent = ocr::extent(&ruby_paragraph.bounds);
+    let mut base_index = None;
+    let mut closest_gap = f32::MAX;
+    for (i, paragraph) in paragraphs.iter().enumerate() {
+        if i == ruby_index || paragraph.symbols.is_empty() {
+            continue;
+        }
+        let base_size = median_size(&paragraph.symbols, paragraph.is_vertical);
+        let ruby_size = median_size(&ruby_paragraph.symbols, paragraph.is_vertical);
+        if ruby_size >= base_size * RUBY_SIZE_RATIO {
+            continue;
+        }
+        let base_extent = ocr::extent(&paragraph.bounds);
+        let (is_aligned, gap) = if paragraph.is_vertical {
+            (ruby_extent.min_y < base_extent.max_y && base_extent.min_y < ruby_extent.max_y, ruby_extent.min_x - base_extent.max_x)
+        } else {
+            (ruby_extent.min_x < base_extent.max_x && base_extent.min_x < ruby_extent.max_x, base_extent.min_y - ruby_extent.max_y)
+        };
+        // Ruby may overlap the base box a little, but not sit past a full line
+        if is_aligned && gap > -base_size && gap < base_size && gap < closest_gap {
+            base_index = Some(i);
+            closest_gap = gap;
+        }
+    }
+    base_index
+}
+
+// ________________________________________
+// Kanji runs of the paragraph replaced by
+// the furigana printed over them, to be
+// used as the reading of the paragraph
+// ----------------------------------------
+pub fn reading(paragraph: &OcrParagraph) -> String {
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    let mut i = 0;
+    while i < paragraph.symbols.len() {
+        if !is_kanji(&paragraph.symbols[i].text) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        // A run ends with the line, so ruby is matched to one line at a time
+        while i < paragraph.symbols.len() && is_kanji(&paragraph.symbols[i].text) {
+            let is_line_end = matches!(paragraph.symbols[i].break_type.as_deref(), Some("LINE_BREAK") | Some("EOL_SURE_SPACE"));
+            i += 1;
+            if is_line_end {
+                break;
+            }
+        }
+        let run_extent = paragraph.symbols[start..i].iter().map(|symbol| ocr::extent(&symbol.bounds)).reduce(ocr::union).unwrap();
+        let ruby_text = ruby_over(paragraph, run_extent);
+        if !ruby_text.is_empty() {
+            replacements.push((start..i, ruby_text));
+        }
+    }
+    paragraph.text_with(&replacements)
+}
+
+fn ruby_over(paragraph: &OcrParagraph, run_extent: Extent) -> String {
+    let run_size = if paragraph.is_vertical { run_extent.max_x - run_extent.min_x } else { run_extent.max_y - run_extent.min_y };
+    let mut ruby: Vec<&OcrSymbol> = paragraph.furigana.iter().filter(|symbol| {
+        let ruby_center = ocr::center(&symbol.bounds);
+        if paragraph.is_vertical {
+            let run_center_x = (run_extent.min_x + run_extent.max_x) / 2.0;
+            ruby_center.y >= run_extent.min_y && ruby_center.y <= run_extent.max_y
+                && ruby_center.x > run_center_x && ruby_center.x < run_extent.max_x + run_size
+        } else {
+            let run_center_y = (run_extent.min_y + run_extent.max_y) / 2.0;
+            ruby_center.x >= run_extent.min_x && ruby_center.x <= run_extent.max_x
+                && ruby_center.y < run_center_y && ruby_center.y > run_extent.min_y - run_size
+        }
+    }).collect();
+    if paragraph.is_vertical {
+        ruby.sort_by(|a, b| ocr::center(&a.bounds).y.total_cmp(&ocr::center(&b.bounds).y));
+    } else {
+        ruby.sort_by(|a, b| ocr::center(&a.bounds).x.total_cmp(&ocr::center(&b.bounds).x));
+    }
+    ruby.iter().map(|symbol| symbol.text.as_str()).collect()
+}
+
+// Glyph size across the writing direction, which ruby shrinks the most
+fn size(symbol: &OcrSymbol, is_vertical: bool) -> f32 {
+    let symbol_extent = ocr::extent(&symbol.bounds);
+    if is_vertical {
+        symbol_extent.max_x - symbol_extent.min_x
+    } else {
+        symbol_extent.max_y - symbol_extent.min_y
+    }
+}
+
+fn median_size(symbols: &[OcrSymbol], is_vertical: bool) -> f32 {
+    let mut sizes: Vec<f32> = symbols.iter().map(|symbol| size(symbol, is_vertical)).collect();
+    if sizes.is_empty() {
+        return 0.0;
+    }
+    sizes.sort_by(|a, b| a.total_cmp(b));
+    sizes[sizes.len() / 2]
+}
+
+fn is_kana(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| matches!(c, '\u{3041}'..='\u{309F}' | '\u{30A0}'..='\u{30FF}'))
+}
+
+// 々 and ヶ are read like kanji and carry ruby of their own
+fn is_kanji(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(reading::is_kanji)
+}
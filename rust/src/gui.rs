@@ -1,5 +1,6 @@
 use godot::prelude::*;
 use godot::classes::{Control, IControl};
+use crate::ocr::{Point, contains_point};
 
 // ============================================================
 // -- sandGUIManager --
@@ -64,3 +65,72 @@ impl sandGUI {
         self.queue_out.push(control);
     }
 }
+
+// ============================================================
+// -- sandOverlay --
+// Outlines OCR regions on top of the capture preview
+// ============================================================
+#[derive(GodotClass)]
+#[class(base = Control)]
+pub struct sandOverlay {
+    #[base]
+    pub node: Base<Control>,
+    pub regions: Vec<Vec<Point>>,
+    pub texture_size: Vector2,
+    pub highlighted: Option<usize>,
+}
+
+#[godot_api]
+impl IControl for sandOverlay {
+    fn init(node: Base<Control>) -> Self {
+        sandOverlay {
+            node,
+            regions: Vec::new(),
+            texture_size: Vector2::ONE,
+            highlighted: None,
+        }
+    }
+
+    fn draw(&mut self) {
+        let scale = self.base().get_size() / self.texture_size;
+        for (i, region) in self.regions.clone().iter().enumerate() {
+            if region.is_empty() { continue; }
+            let mut points = PackedVector2Array::new();
+            for point in region.iter().chain(region.first()) {
+                points.push(Vector2::new(point.x, point.y) * scale);
+            }
+            if self.highlighted == Some(i) {
+                self.base_mut().draw_polyline_ex(&points, Color::from_rgba(0.98, 0.87, 0.85, 1.0)).width(2.0).done();
+            } else {
+                self.base_mut().draw_polyline(&points, Color::from_rgba(0.98, 0.87, 0.85, 0.25));
+            }
+        }
+    }
+}
+
+#[godot_api]
+impl sandOverlay {
+    pub fn set_regions(&mut self, regions: Vec<Vec<Point>>, texture_size: Vector2) {
+        self.regions = regions;
+        self.texture_size = texture_size;
+        self.highlighted = None;
+        self.base_mut().queue_redraw();
+    }
+
+    // Region under the mouse cursor, if the preview is on screen
+    pub fn region_at_mouse(&self) -> Option<usize> {
+        if !self.base().is_visible_in_tree() { return None; }
+        let mouse_position = self.base().get_local_mouse_position();
+        if !Rect2::new(Vector2::ZERO, self.base().get_size()).contains_point(mouse_position) { return None; }
+        let position = mouse_position / (self.base().get_size() / self.texture_size);
+        let point = Point { x: position.x, y: position.y };
+        self.regions.iter().position(|region| contains_point(region, point))
+    }
+
+    pub fn highlight(&mut self, index: Option<usize>) {
+        if self.highlighted != index {
+            self.highlighted = index;
+            self.base_mut().queue_redraw();
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+const HISTORY_CAPACITY: usize = 20;
+
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp: String,
+    // The crop before preprocessing
+    pub png_bytes: Vec<u8>,
+}
+
+// ============================================================
+// -- CaptureHistory --
+// Ring buffer of the last captures sent to OCR, newest first
+// ============================================================
+pub struct CaptureHistory {
+    entries: VecDeque<HistoryEntry>,
+    next_id: u64,
+}
+
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaptureHistory {
+    pub fn new() -> Self {
+        CaptureHistory {
+            entries: VecDeque::with_capacity(HISTORY_CAPACITY),
+            next_id: 0,
+        }
+    }
+
+    pub fn push(&mut self, timestamp: String, png_bytes: Vec<u8>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.entries.len() == HISTORY_CAPACITY {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(HistoryEntry { id, timestamp, png_bytes });
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&HistoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+}
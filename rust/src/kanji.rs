@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+// ________________________________________
+// KANJIDIC2 in the jmdict-simplified JSON
+// layout (kanjidic2-en-*.json), which the
+// user saves as res://dict/kanjidic2-en.json
+// ----------------------------------------
+#[derive(Deserialize)]
+struct KanjidicFile {
+    characters: Vec<Character>,
+}
+
+#[derive(Deserialize)]
+struct Character {
+    literal: String,
+    #[serde(default)]
+    misc: Misc,
+    #[serde(default, rename = "readingMeaning")]
+    reading_meaning: Option<ReadingMeaning>,
+}
+
+#[derive(Deserialize, Default)]
+struct Misc {
+    #[serde(default)]
+    grade: Option<u32>,
+    #[serde(default, rename = "strokeCounts")]
+    stroke_counts: Vec<u32>,
+    #[serde(default)]
+    frequency: Option<u32>,
+    #[serde(default, rename = "jlptLevel")]
+    jlpt_level: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct ReadingMeaning {
+    #[serde(default)]
+    groups: Vec<Group>,
+    #[serde(default)]
+    nanori: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Group {
+    #[serde(default)]
+    readings: Vec<Reading>,
+    #[serde(default)]
+    meanings: Vec<Meaning>,
+}
+
+#[derive(Deserialize)]
+struct Reading {
+    #[serde(rename = "type")]
+    kind: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Meaning {
+    #[serde(default)]
+    lang: String,
+    value: String,
+}
+
+pub struct Kanji {
+    pub literal: char,
+    pub on: Vec<String>,
+    pub kun: Vec<String>,
+    pub nanori: Vec<String>,
+    pub meanings: Vec<String>,
+    pub strokes: Option<u32>,
+    pub grade: Option<u32>,
+    // Old four-level JLPT, 4 being the easiest
+    pub jlpt: Option<u32>,
+    // Rank among the 2500 most used kanji in newspapers
+    pub frequency: Option<u32>,
+}
+
+pub struct Kanjidic {
+    characters: HashMap<char, Kanji>,
+}
+
+impl Kanjidic {
+    pub fn from_json(bytes: &[u8]) -> Result<Self, String> {
+        let file: KanjidicFile = serde_json::from_slice(bytes).map_err(|e| format!("The KANJIDIC2 file could not be read: {}", e))?;
+        let mut characters = HashMap::new();
+        for character in file.characters {
+            let Some(literal) = character.literal.chars().next() else {
+                continue;
+            };
+            let mut kanji = Kanji {
+                literal,
+                on: Vec::new(),
+                kun: Vec::new(),
+                nanori: Vec::new(),
+                meanings: Vec::new(),
+                strokes: character.misc.stroke_counts.first().copied(),
+                grade: character.misc.grade,
+                jlpt: character.misc.jlpt_level,
+                frequency: character.misc.frequency,
+            };
+            if let Some(reading_meaning) = character.reading_meaning {
+                for group in reading_meaning.groups {
+                    for reading in group.readings {
+                        match reading.kind.as_str() {
+                            "ja_on" => kanji.on.push(reading.value),
+                            "ja_kun" => kanji.kun.push(reading.value),
+                            _ => {},
+                        }
+                    }
+                    kanji.meanings.extend(group.meanings.into_iter().filter(|meaning| meaning.lang.is_empty() || meaning.lang == "en").map(|meaning| meaning.value));
+                }
+                kanji.nanori = reading_meaning.nanori;
+            }
+            characters.insert(literal, kanji);
+        }
+        Ok(Kanjidic { characters })
+    }
+
+    pub fn get(&self, literal: char) -> Option<&Kanji> {
+        self.characters.get(&literal)
+    }
+}
+
+// ________________________________________
+// BBCode for the kanji panel. Kun readings
+// keep KANJIDIC's dot between stem and
+// okurigana, shown as 食(べる)
+// ----------------------------------------
+pub fn format_kanji(kanji: &Kanji) -> String {
+    let mut lines = vec![format!("[font_size=48]{}[/font_size]", kanji.literal)];
+    lines.push(kanji.meanings.join(", ").replace('[', "[lb]"));
+    lines.push(String::new());
+    if !kanji.on.is_empty() {
+        lines.push(format!("[color=#777777]On[/color]  {}", kanji.on.join("、")));
+    }
+    if !kanji.kun.is_empty() {
+        let kun: Vec<String> = kanji.kun.iter().map(|kun| match kun.split_once('.') {
+            Some((stem, okurigana)) => format!("{}({})", stem, okurigana),
+            None => kun.clone(),
+        }).collect();
+        lines.push(format!("[color=#777777]Kun[/color]  {}", kun.join("、")));
+    }
+    if !kanji.nanori.is_empty() {
+        lines.push(format!("[color=#777777]Names[/color]  {}", kanji.nanori.join("、")));
+    }
+    lines.push(String::new());
+    let mut details = Vec::new();
+    if let Some(strokes) = kanji.strokes {
+        details.push(format!("{} strokes", strokes));
+    }
+    if let Some(grade) = kanji.grade {
+        details.push(format!("Grade {}", grade));
+    }
+    if let Some(jlpt) = kanji.jlpt {
+        details.push(format!("JLPT {} (old)", jlpt));
+    }
+    match kanji.frequency {
+        Some(frequency) => details.push(format!("Frequency #{}", frequency)),
+        None => details.push("Uncommon".to_string()),
+    }
+    lines.push(format!("[color=#777777]{}[/color]", details.join("  ·  ")));
+    lines.join("\n")
+}
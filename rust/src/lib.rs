@@ -13,6 +13,26 @@ unsafe impl ExtensionLibrary for Rust {}
 // ------ MODULE IMPORT ------
 mod system;
 mod gui;
+mod capture;
+mod preprocess;
+mod history;
+mod ocr;
+mod segment;
+mod furigana;
+mod rules;
+mod normalize;
+mod speaker;
+mod reading;
+mod tokenizer;
+mod deinflect;
+mod dictionary;
+mod kanji;
+mod difficulty;
+mod anki;
+mod tts;
+mod pitch;
+#[cfg(target_os = "linux")]
+mod wayland;
 
 // ------ UTILITY FUNCTIONS ------
 pub mod utils {
@@ -0,0 +1,223 @@
+use serde::{Serialize, Deserialize};
+use unicode_normalization::UnicodeNormalization;
+use crate::ocr::is_cjk;
+use crate::reading::is_kanji;
+
+// Kanji and katakana that OCR mixes up, told apart by the script around them
+const CONFUSABLES: [(char, char); 9] = [
+    ('一', 'ー'),
+    ('口', 'ロ'),
+    ('力', 'カ'),
+    ('工', 'エ'),
+    ('二', 'ニ'),
+    ('八', 'ハ'),
+    ('夕', 'タ'),
+    ('卜', 'ト'),
+    ('千', 'チ'),
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NormalizeConfig {
+    pub nfkc: bool,
+    pub confusables: bool,
+    pub ellipsis: bool,
+    pub dashes: bool,
+    pub stray_marks: bool,
+}
+
+impl Default for NormalizeConfig {
+    fn default() -> Self {
+        NormalizeConfig {
+            nfkc: true,
+            confusables: true,
+            ellipsis: true,
+            dashes: true,
+            stray_marks: true,
+        }
+    }
+}
+
+pub struct Change {
+    pub rule: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+// ________________________________________
+// Clean up OCR artifacts before romaji and
+// translation. Every change is reported so
+// the Settings tab can show what each rule
+// did to the last reading
+// ----------------------------------------
+pub fn normalize(text: &str, config: &NormalizeConfig) -> (String, Vec<Change>) {
+    let mut changes = Vec::new();
+    let mut normalized = text.to_string();
+    if config.nfkc {
+        normalized = nfkc(&normalized, &mut changes);
+    }
+    if config.confusables {
+        normalized = fix_confusables(&normalized, &mut changes);
+    }
+    if config.ellipsis {
+        normalized = unify_ellipsis(&normalized, &mut changes);
+    }
+    if config.dashes {
+        normalized = unify_dashes(&normalized, &mut changes);
+    }
+    if config.stray_marks {
+        normalized = remove_stray_marks(&normalized, &mut changes);
+    }
+    (normalized, changes)
+}
+
+// Full-width Latin to ASCII and half-width katakana to full-width, but
+// Japanese punctuation keeps its full-width form, and the ellipsis and
+// wave dash are left for their own rules
+fn nfkc(text: &str, changes: &mut Vec<Change>) -> String {
+    let mut normalized = String::new();
+    let mut run = String::new();
+    for c in text.chars() {
+        if matches!(c, '…' | '‥' | '～' | '〜') || is_fullwidth_punctuation(c) {
+            normalized.extend(run.nfkc());
+            run.clear();
+            normalized.push(c);
+        } else {
+            run.push(c);
+        }
+    }
+    normalized.extend(run.nfkc());
+    if normalized != text {
+        record(changes, "NFKC", text, &normalized);
+    }
+    normalized
+}
+
+fn fix_confusables(text: &str, changes: &mut Vec<Change>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut fixed = chars.clone();
+    for i in 0..chars.len() {
+        let pair = CONFUSABLES.iter().find(|(kanji, katakana)| chars[i] == *kanji || chars[i] == *katakana);
+        let (kanji, katakana) = match pair {
+            Some(pair) => *pair,
+            None => continue,
+        };
+        // Neighbours that are confusable themselves say nothing, so look past them
+        let before = chars[..i].iter().rev().find(|c| !is_confusable(**c)).copied();
+        let after = chars[i + 1..].iter().find(|c| !is_confusable(**c)).copied();
+        let katakana_count = [before, after].iter().filter(|c| c.is_some_and(is_katakana)).count();
+        let kanji_count = [before, after].iter().filter(|c| c.is_some_and(is_kanji)).count();
+        if chars[i] == kanji && katakana_count > 0 && kanji_count == 0 {
+            fixed[i] = katakana;
+        } else if chars[i] == katakana && kanji_count > 0 && katakana_count == 0 {
+            // カ after a number is the counter of 3カ月 or 何カ所
+            if katakana == 'カ' && i > 0 && is_numeral(chars[i - 1]) {
+                continue;
+            }
+            // ー only ever follows a sound, so a lone ー after a kanji stays
+            if katakana == 'ー' && after.is_none_or(|c| !is_kanji(c)) {
+                continue;
+            }
+            fixed[i] = kanji;
+        }
+    }
+    let fixed: String = fixed.into_iter().collect();
+    if fixed != text {
+        record(changes, "Confusables", text, &fixed);
+    }
+    fixed
+}
+
+fn unify_ellipsis(text: &str, changes: &mut Vec<Change>) -> String {
+    let mut unified = text.to_string();
+    for dots in ["・・・", "･･･", "。。。", "．．．", "...", "‥"] {
+        unified = unified.replace(dots, "…");
+    }
+    if unified != text {
+        record(changes, "Ellipsis", text, &unified);
+    }
+    unified
+}
+
+// Long dashes all become ―, and wave dashes become 〜
+fn unify_dashes(text: &str, changes: &mut Vec<Change>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut unified = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let is_japanese_before = i > 0 && is_cjk(chars[i - 1]);
+        match c {
+            '—' | '–' | '─' | '━' | '−' => unified.push('―'),
+            '~' | '～' if is_japanese_before => unified.push('〜'),
+            _ => unified.push(*c),
+        }
+    }
+    if unified != text {
+        record(changes, "Dashes", text, &unified);
+    }
+    unified
+}
+
+// Specks that OCR reads as quotes or accents next to Japanese text
+fn remove_stray_marks(text: &str, changes: &mut Vec<Change>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut cleaned = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let is_stray = matches!(c, '\'' | '`' | '´' | '¨' | '^' | '|')
+            && (i > 0 && is_cjk(chars[i - 1]) || chars.get(i + 1).is_some_and(|next| is_cjk(*next)));
+        if !is_stray {
+            cleaned.push(*c);
+        }
+    }
+    if cleaned != text {
+        record(changes, "Stray Marks", text, &cleaned);
+    }
+    cleaned
+}
+
+fn record(changes: &mut Vec<Change>, rule: &'static str, before: &str, after: &str) {
+    changes.push(Change {
+        rule,
+        before: before.to_string(),
+        after: after.to_string(),
+    });
+}
+
+// ！？（）：and the ideographic space, which NFKC would turn into ASCII
+fn is_fullwidth_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}' | '\u{FF01}'..='\u{FF0F}' | '\u{FF1A}'..='\u{FF20}' | '\u{FF3B}'..='\u{FF40}' | '\u{FF5B}'..='\u{FF60}')
+}
+
+fn is_numeral(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, '０'..='９' | '〇' | '一' | '二' | '三' | '四' | '五' | '六' | '七' | '八' | '九' | '十' | '百' | '千' | '万' | '何' | '数' | '幾')
+}
+
+fn is_confusable(c: char) -> bool {
+    CONFUSABLES.iter().any(|(kanji, katakana)| c == *kanji || c == *katakana)
+}
+
+// ヵ and ヶ are left out: as in 一ヶ月 they are counters, written next to kanji
+fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A1}'..='\u{30F4}' | '\u{30F7}'..='\u{30FA}' | 'ー' | '\u{31F0}'..='\u{31FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confusables_fixed(text: &str) -> String {
+        fix_confusables(text, &mut Vec::new())
+    }
+
+    #[test]
+    fn counters_keep_their_numerals() {
+        assert_eq!(confusables_fixed("一ヶ月"), "一ヶ月");
+        assert_eq!(confusables_fixed("一ヵ所"), "一ヵ所");
+        assert_eq!(confusables_fixed("三カ月"), "三カ月");
+    }
+
+    #[test]
+    fn confusables_follow_their_script() {
+        assert_eq!(confusables_fixed("メ口ン"), "メロン");
+        assert_eq!(confusables_fixed("入ロ"), "入口");
+        assert_eq!(confusables_fixed("ス一パー"), "スーパー");
+    }
+}
@@ -0,0 +1,298 @@
+use std::ops::Range;
+use serde_json::Value;
+
+// Position in the coordinates of the captured crop, before preprocessing upscaled it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone)]
+pub struct OcrSymbol {
+    pub text: String,
+    pub bounds: Vec<Point>,
+    pub break_type: Option<String>,
+    pub confidence: f32,
+}
+
+pub struct OcrParagraph {
+    pub symbols: Vec<OcrSymbol>,
+    pub bounds: Vec<Point>,
+    pub is_vertical: bool,
+    // Ruby text taken out of the sentence, see furigana.rs
+    pub furigana: Vec<OcrSymbol>,
+}
+
+impl OcrParagraph {
+    pub fn text(&self) -> String {
+        self.text_with(&[])
+    }
+
+    // Same as text(), with runs of symbols swapped for other text
+    pub fn text_with(&self, replacements: &[(Range<usize>, String)]) -> String {
+        self.assemble(replacements).0
+    }
+
+    // OCR confidence of every character of text()
+    pub fn char_confidences(&self) -> Vec<f32> {
+        self.assemble(&[]).1
+    }
+
+    pub fn first_line(&self) -> String {
+        let mut line_text = String::new();
+        for symbol in &self.symbols {
+            line_text.push_str(&symbol.text);
+            if matches!(symbol.break_type.as_deref(), Some("LINE_BREAK") | Some("EOL_SURE_SPACE")) {
+                break;
+            }
+        }
+        line_text
+    }
+
+    pub fn confidence(&self) -> f32 {
+        if self.symbols.is_empty() {
+            return 0.0;
+        }
+        self.symbols.iter().map(|symbol| symbol.confidence).sum::<f32>() / self.symbols.len() as f32
+    }
+
+    fn assemble(&self, replacements: &[(Range<usize>, String)]) -> (String, Vec<f32>) {
+        let mut block_text = String::new();
+        let mut confidences = Vec::new();
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            match replacements.iter().find(|(range, _)| range.contains(&i)) {
+                Some((range, replacement)) => {
+                    if i == range.start {
+                        block_text.push_str(replacement);
+                        confidences.extend(replacement.chars().map(|_| 1.0));
+                    }
+                    if i + 1 < range.end {
+                        continue;
+                    }
+                },
+                None => {
+                    block_text.push_str(&symbol.text);
+                    confidences.extend(symbol.text.chars().map(|_| symbol.confidence));
+                },
+            }
+            let next_symbol = match self.symbols.get(i + 1) {
+                Some(next_symbol) => next_symbol,
+                None => break,
+            };
+            // Japanese does not separate words or wrapped lines with spaces, so a
+            // break only becomes a space where Latin text sits on a side of it
+            let is_cjk_before = symbol.text.chars().last().is_some_and(is_cjk);
+            let is_cjk_after = next_symbol.text.chars().next().is_some_and(is_cjk);
+            match symbol.break_type.as_deref() {
+                // Word wrapped with a hyphen that Vision leaves out of the text
+                Some("HYPHEN") | None => {},
+                Some("EOL_SURE_SPACE") | Some("LINE_BREAK") => {
+                    if !is_cjk_before && !is_cjk_after {
+                        block_text.push(' ');
+                        confidences.push(1.0);
+                    }
+                },
+                // SPACE, SURE_SPACE and UNKNOWN
+                Some(_) => {
+                    if !is_cjk_before || !is_cjk_after {
+                        block_text.push(' ');
+                        confidences.push(1.0);
+                    }
+                },
+            }
+        }
+        (block_text, confidences)
+    }
+}
+
+// Kana, kanji, CJK punctuation and full-width forms
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}' // CJK symbols and punctuation
+        | '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+        | '\u{31F0}'..='\u{31FF}' // Katakana phonetic extensions
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FF00}'..='\u{FFEF}' // Half-width and full-width forms
+        | '\u{20000}'..='\u{2FA1F}' // CJK extensions B and beyond
+    )
+}
+
+// Axis-aligned extent of a polygon
+#[derive(Clone, Copy)]
+pub struct Extent {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+// ________________________________________
+// Flatten a Vision DOCUMENT_TEXT_DETECTION
+// response into its paragraphs. Vision saw
+// the crop upscaled, its bounds are scaled
+// back down
+// ----------------------------------------
+pub fn read_paragraphs(response_json: &Value, upscale: u32) -> Vec<OcrParagraph> {
+    let mut ocr_paragraphs = Vec::new();
+    if let Some(pages) = response_json["responses"][0]["fullTextAnnotation"]["pages"].as_array() {
+        for page in pages {
+            if let Some(blocks) = page["blocks"].as_array() {
+                for block in blocks {
+                    if let Some(paragraphs) = block["paragraphs"].as_array() {
+                        for paragraph in paragraphs {
+                            let mut symbols = Vec::new();
+                            for word in paragraph["words"].as_array().unwrap() {
+                                for symbol in word["symbols"].as_array().unwrap() {
+                                    symbols.push(OcrSymbol {
+                                        text: symbol["text"].as_str().unwrap().to_string(),
+                                        bounds: parse_bounding_box(&symbol["boundingBox"], upscale),
+                                        break_type: symbol["property"]["detectedBreak"]["type"].as_str().map(|t| t.to_string()),
+                                        confidence: symbol["confidence"].as_f64().unwrap_or(1.0) as f32,
+                                    });
+                                }
+                            }
+                            let bounds = parse_bounding_box(&paragraph["boundingBox"], upscale);
+                            let is_vertical = is_vertical(&symbols, &bounds);
+                            ocr_paragraphs.push(OcrParagraph {
+                                symbols,
+                                bounds,
+                                is_vertical,
+                                furigana: Vec::new(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ocr_paragraphs
+}
+
+// Vision leaves out coordinates that are 0
+pub fn parse_bounding_box(bounding_box: &Value, upscale: u32) -> Vec<Point> {
+    let scale = upscale.max(1) as f32;
+    let mut points = Vec::new();
+    if let Some(vertices) = bounding_box["vertices"].as_array() {
+        for vertex in vertices {
+            points.push(Point {
+                x: vertex["x"].as_f64().unwrap_or(0.0) as f32 / scale,
+                y: vertex["y"].as_f64().unwrap_or(0.0) as f32 / scale,
+            });
+        }
+    }
+    points
+}
+
+pub fn contains_point(polygon: &[Point], point: Point) -> bool {
+    let mut is_inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > point.y) != (b.y > point.y) && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x {
+            is_inside = !is_inside;
+        }
+        j = i;
+    }
+    is_inside
+}
+
+// ________________________________________
+// Layout analysis: sort paragraphs into
+// reading order. Vertical (tategaki) text
+// reads in columns from right to left,
+// horizontal text in rows from the top
+// ----------------------------------------
+pub fn sort_reading_order(paragraphs: &mut Vec<OcrParagraph>) {
+    let vertical_count = paragraphs.iter().filter(|p| p.is_vertical).count();
+    let is_vertical_layout = vertical_count * 2 > paragraphs.len();
+    // Lines are grouped along the cross axis, then read along the main axis
+    let mut lines: Vec<(Extent, Vec<OcrParagraph>)> = Vec::new();
+    let mut sorted: Vec<OcrParagraph> = std::mem::take(paragraphs);
+    if is_vertical_layout {
+        sorted.sort_by(|a, b| extent(&b.bounds).max_x.total_cmp(&extent(&a.bounds).max_x));
+    } else {
+        sorted.sort_by(|a, b| extent(&a.bounds).min_y.total_cmp(&extent(&b.bounds).min_y));
+    }
+    for paragraph in sorted {
+        let paragraph_extent = extent(&paragraph.bounds);
+        let line = lines.iter_mut().find(|(line_extent, _)| {
+            if is_vertical_layout {
+                let center_x = (paragraph_extent.min_x + paragraph_extent.max_x) / 2.0;
+                center_x >= line_extent.min_x && center_x <= line_extent.max_x
+            } else {
+                let center_y = (paragraph_extent.min_y + paragraph_extent.max_y) / 2.0;
+                center_y >= line_extent.min_y && center_y <= line_extent.max_y
+            }
+        });
+        match line {
+            Some((line_extent, line_paragraphs)) => {
+                *line_extent = union(*line_extent, paragraph_extent);
+                line_paragraphs.push(paragraph);
+            },
+            None => lines.push((paragraph_extent, vec![paragraph])),
+        }
+    }
+    for (_, mut line_paragraphs) in lines {
+        if is_vertical_layout {
+            line_paragraphs.sort_by(|a, b| extent(&a.bounds).min_y.total_cmp(&extent(&b.bounds).min_y));
+        } else {
+            line_paragraphs.sort_by(|a, b| extent(&a.bounds).min_x.total_cmp(&extent(&b.bounds).min_x));
+        }
+        paragraphs.extend(line_paragraphs);
+    }
+}
+
+// Writing direction from how the symbols progress through the paragraph,
+// falling back to the paragraph's shape for single characters
+fn is_vertical(symbols: &[OcrSymbol], bounds: &[Point]) -> bool {
+    if symbols.len() >= 2 {
+        let first = center(&symbols[0].bounds);
+        let last = center(&symbols[symbols.len() - 1].bounds);
+        return (last.y - first.y).abs() > (last.x - first.x).abs();
+    }
+    let paragraph_extent = extent(bounds);
+    (paragraph_extent.max_y - paragraph_extent.min_y) > (paragraph_extent.max_x - paragraph_extent.min_x) * 1.5
+}
+
+pub fn center(polygon: &[Point]) -> Point {
+    let polygon_extent = extent(polygon);
+    Point {
+        x: (polygon_extent.min_x + polygon_extent.max_x) / 2.0,
+        y: (polygon_extent.min_y + polygon_extent.max_y) / 2.0,
+    }
+}
+
+pub fn extent(polygon: &[Point]) -> Extent {
+    if polygon.is_empty() {
+        return Extent { min_x: 0.0, min_y: 0.0, max_x: 0.0, max_y: 0.0 };
+    }
+    let mut polygon_extent = Extent { min_x: f32::MAX, min_y: f32::MAX, max_x: f32::MIN, max_y: f32::MIN };
+    for point in polygon {
+        polygon_extent.min_x = polygon_extent.min_x.min(point.x);
+        polygon_extent.min_y = polygon_extent.min_y.min(point.y);
+        polygon_extent.max_x = polygon_extent.max_x.max(point.x);
+        polygon_extent.max_y = polygon_extent.max_y.max(point.y);
+    }
+    polygon_extent
+}
+
+pub fn union(a: Extent, b: Extent) -> Extent {
+    Extent {
+        min_x: a.min_x.min(b.min_x),
+        min_y: a.min_y.min(b.min_y),
+        max_x: a.max_x.max(b.max_x),
+        max_y: a.max_y.max(b.max_y),
+    }
+}
+
+pub fn to_polygon(polygon_extent: Extent) -> Vec<Point> {
+    vec![
+        Point { x: polygon_extent.min_x, y: polygon_extent.min_y },
+        Point { x: polygon_extent.max_x, y: polygon_extent.min_y },
+        Point { x: polygon_extent.max_x, y: polygon_extent.max_y },
+        Point { x: polygon_extent.min_x, y: polygon_extent.max_y },
+    ]
+}
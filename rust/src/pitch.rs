@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use crate::reading::to_hiragana;
+use crate::tokenizer::Word;
+
+// Option order in the Pitch Accent list, 1 writes downstep marks only
+pub const PITCH_OFF: i32 = 0;
+pub const PITCH_OVERLINE: i32 = 2;
+
+// Written after the mora the pitch falls from
+const DOWNSTEP_MARK: char = 'ꜜ';
+const COMBINING_OVERLINE: char = '\u{0305}';
+
+// Readings of a word with the mora each one falls after, 0 for flat words
+pub type AccentDictionary = HashMap<String, Vec<(String, Vec<u32>)>>;
+
+// ________________________________________
+// Kanjium's accents.txt layout: word, its
+// reading when it is not kana already, and
+// the accents, as in 箸 / はし / 1. Several
+// accents are comma separated, part of
+// speech notes like (名) are skipped
+// ----------------------------------------
+pub fn parse_accents(bytes: &[u8]) -> Result<AccentDictionary, String> {
+    let text = String::from_utf8(bytes.to_vec()).map_err(|_| "The pitch accent list is not UTF-8 text".to_string())?;
+    let mut accents: AccentDictionary = HashMap::new();
+    for line in text.lines() {
+        let columns: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let (word, reading, accent_text) = match columns.as_slice() {
+            [word, reading, accent_text, ..] => (*word, if reading.is_empty() { *word } else { *reading }, *accent_text),
+            [word, accent_text] => (*word, *word, *accent_text),
+            _ => continue,
+        };
+        let downsteps: Vec<u32> = accent_text.split(',')
+            .filter_map(|accent| accent.chars().filter(char::is_ascii_digit).collect::<String>().parse::<u32>().ok())
+            .collect();
+        if word.is_empty() || downsteps.is_empty() {
+            continue;
+        }
+        let reading: String = reading.chars().map(to_hiragana).collect();
+        accents.entry(word.to_string()).or_default().push((reading, downsteps));
+    }
+    Ok(accents)
+}
+
+// ________________________________________
+// Accent of a word as it appears in the
+// line. Conjugated forms move the accent,
+// so only words in their dictionary form
+// are looked up
+// ----------------------------------------
+pub fn word_accent(word: &Word, accents: &AccentDictionary) -> Option<u32> {
+    if !matches!(word.pos.as_str(), "名詞" | "動詞" | "形容詞" | "副詞" | "連体詞" | "感動詞" | "接続詞") || word.surface != word.base_form {
+        return None;
+    }
+    let entries = accents.get(&word.surface)?;
+    // Words like 今日 have more than one reading, each with its own accent
+    let (_, downsteps) = entries.iter().find(|(reading, _)| *reading == word.reading).or_else(|| entries.first())?;
+    downsteps.first().copied()
+}
+
+// Small kana belong to the mora before them
+fn morae(kana_text: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in kana_text.chars() {
+        match (to_hiragana(c), morae.last_mut()) {
+            ('ゃ' | 'ゅ' | 'ょ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ゎ', Some(mora)) => mora.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    morae
+}
+
+// ________________________________________
+// Mark a reading with its accent. Each run
+// of high or low morae is converted to the
+// reading format as a whole, so romaji
+// still joins っ and ん with their
+// neighbours
+// ----------------------------------------
+pub fn annotate<F>(kana_text: &str, downstep: u32, style: i32, convert: F) -> String where F: Fn(&str) -> String {
+    let morae = morae(kana_text);
+    let downstep = downstep as usize;
+    if morae.is_empty() || downstep > morae.len() {
+        return convert(kana_text);
+    }
+    // Heiban and nakadaka words start low, atamadaka words start high
+    let is_high = |i: usize| match downstep {
+        0 => i > 0,
+        1 => i == 0,
+        _ => i > 0 && i < downstep,
+    };
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    for (i, mora) in morae.iter().enumerate() {
+        match runs.last_mut() {
+            Some((run, run_high)) if *run_high == is_high(i) => run.push_str(mora),
+            _ => runs.push((mora.clone(), is_high(i))),
+        }
+    }
+    let mut annotated = String::new();
+    for (run, run_high) in runs {
+        let converted = convert(&run);
+        if run_high && style == PITCH_OVERLINE {
+            annotated.extend(converted.chars().flat_map(|c| [c, COMBINING_OVERLINE]));
+        } else {
+            annotated.push_str(&converted);
+        }
+        if run_high && downstep > 0 {
+            annotated.push(DOWNSTEP_MARK);
+        }
+    }
+    annotated
+}
@@ -0,0 +1,140 @@
+use image::{RgbaImage, Rgba, imageops};
+use image::imageops::FilterType;
+use serde::{Serialize, Deserialize};
+
+// ============================================================
+// -- PreprocessConfig --
+// Image processing chain applied to the cropped reading area
+// before it is encoded and sent to OCR
+// ============================================================
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PreprocessConfig {
+    pub upscale: u32,
+    pub upscale_filter: i32,
+    pub grayscale: bool,
+    pub contrast: f32,
+    pub threshold: u8,
+    pub invert: bool,
+    pub denoise: bool,
+    pub color_key: Option<[u8; 3]>,
+    pub color_key_tolerance: u8,
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        PreprocessConfig {
+            upscale: 1,
+            upscale_filter: 0,
+            grayscale: false,
+            contrast: 0.0,
+            threshold: 0,
+            invert: false,
+            denoise: false,
+            color_key: None,
+            color_key_tolerance: 32,
+        }
+    }
+}
+
+pub fn preprocess(image: RgbaImage, config: &PreprocessConfig) -> RgbaImage {
+    let mut image = image;
+    // -- COLOUR KEY --
+    if let Some(color_key) = config.color_key {
+        image = extract_color_key(&image, color_key, config.color_key_tolerance);
+    }
+    // -- UPSCALE --
+    if config.upscale > 1 {
+        let filter = if config.upscale_filter == 1 { FilterType::Lanczos3 } else { FilterType::Nearest };
+        image = imageops::resize(&image, image.width() * config.upscale, image.height() * config.upscale, filter);
+    }
+    // -- GRAYSCALE --
+    if config.grayscale {
+        for pixel in image.pixels_mut() {
+            let luma = luminance(pixel);
+            *pixel = Rgba([luma, luma, luma, pixel[3]]);
+        }
+    }
+    // -- CONTRAST --
+    if config.contrast != 0.0 {
+        image = imageops::contrast(&image, config.contrast);
+    }
+    // -- THRESHOLD --
+    if config.threshold > 0 {
+        for pixel in image.pixels_mut() {
+            let value = if luminance(pixel) >= config.threshold { 255 } else { 0 };
+            *pixel = Rgba([value, value, value, pixel[3]]);
+        }
+    }
+    // -- INVERT --
+    if config.invert {
+        imageops::invert(&mut image);
+    }
+    // -- DENOISE --
+    if config.denoise {
+        image = median_filter(&image);
+    }
+    image
+}
+
+// Parse "#RRGGBB" or "RRGGBB"; an empty string disables the colour key
+pub fn parse_color_key(text: &str) -> Option<[u8; 3]> {
+    let hex = text.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some([r, g, b])
+}
+
+pub fn format_color_key(color_key: Option<[u8; 3]>) -> String {
+    match color_key {
+        Some([r, g, b]) => format!("#{:02X}{:02X}{:02X}", r, g, b),
+        None => String::new(),
+    }
+}
+
+fn luminance(pixel: &Rgba<u8>) -> u8 {
+    (0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32) as u8
+}
+
+// Pixels close to the key colour become black text, everything else a white background
+fn extract_color_key(image: &RgbaImage, color_key: [u8; 3], tolerance: u8) -> RgbaImage {
+    let mut extracted = image.clone();
+    for pixel in extracted.pixels_mut() {
+        let is_text = (0..3).all(|c| (pixel[c] as i16 - color_key[c] as i16).unsigned_abs() <= tolerance as u16);
+        let value = if is_text { 0 } else { 255 };
+        *pixel = Rgba([value, value, value, 255]);
+    }
+    extracted
+}
+
+// 3x3 median per channel: removes isolated specks without blurring glyph edges much
+fn median_filter(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut filtered = image.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let mut channels = [[0u8; 9]; 3];
+            let mut count = 0;
+            for dy in -1i32..=1 {
+                for dx in -1i32..=1 {
+                    let nx = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                    let ny = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                    let neighbour = image.get_pixel(nx, ny);
+                    for c in 0..3 {
+                        channels[c][count] = neighbour[c];
+                    }
+                    count += 1;
+                }
+            }
+            let pixel = filtered.get_pixel_mut(x, y);
+            for c in 0..3 {
+                channels[c].sort_unstable();
+                pixel[c] = channels[c][4];
+            }
+        }
+    }
+    filtered
+}
@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use crate::tokenizer::Word;
+use crate::pitch::{self, PITCH_OFF};
+
+// ------ READING FORMATS ------
+// The tokenizer, or KAKASI without it, gives the reading of a line as
+// hiragana. Everything shown in the reading line is derived from that and
+// the original text, so switching formats needs no new reading. Words the
+// learner marked as known are left as written, and words with a pitch accent
+// are marked with it.
+
+// Option order in the Reading Format list, 1 is Kunrei-shiki
+pub const HEPBURN: i32 = 0;
+pub const NIHON_SHIKI: i32 = 2;
+pub const HIRAGANA: i32 = 3;
+pub const KANA: i32 = 4;
+pub const KATAKANA: i32 = 5;
+
+// A piece of the original text, with its reading if it holds kanji
+pub struct Segment {
+    pub text: String,
+    pub reading: Option<String>,
+}
+
+// Accents line up with words, None where a word has no accent to show
+pub fn format_reading(jp_text: &str, jp_kana: &str, words: &[Word], known_words: &HashSet<String>, accents: &[Option<u32>], pitch_style: i32, reading_format: i32) -> String {
+    if !words.is_empty() {
+        let is_romaji = !matches!(reading_format, HIRAGANA | KANA | KATAKANA);
+        let mut reading_text = String::new();
+        for (i, word) in words.iter().enumerate() {
+            let accent = accents.get(i).copied().flatten().filter(|_| pitch_style != PITCH_OFF);
+            let word_reading = match (word.pos.as_str(), word.surface.as_str(), accent) {
+                _ if word.is_known(known_words) => word.surface.clone(),
+                _ if reading_format == HIRAGANA => {
+                    let written_reading = hiragana_reading(&word.surface, &word.reading);
+                    match accent {
+                        // Kanji left over mean the reading did not line up, so the morae can't be counted
+                        Some(downstep) if !written_reading.chars().any(is_kanji) => pitch::annotate(&written_reading, downstep, pitch_style, |kana_text| kana_text.to_string()),
+                        _ => written_reading,
+                    }
+                },
+                (_, _, Some(downstep)) => pitch::annotate(&word.reading, downstep, pitch_style, |kana_text| ruby_reading(kana_text, reading_format)),
+                // Particles are spelled as they sound
+                ("助詞", "は", _) if is_romaji => "wa".to_string(),
+                ("助詞", "へ", _) if is_romaji => "e".to_string(),
+                _ => ruby_reading(&word.reading, reading_format),
+            };
+            if is_romaji && !reading_text.is_empty() && !reading_text.ends_with(' ') && !word.is_punctuation() {
+                reading_text.push(' ');
+            }
+            reading_text.push_str(&word_reading);
+        }
+        return reading_text.trim().to_string();
+    }
+    match reading_format {
+        HIRAGANA => hiragana_reading(jp_text, jp_kana),
+        KANA => jp_kana.chars().map(to_hiragana).collect(),
+        KATAKANA => jp_kana.chars().map(to_katakana).collect(),
+        _ => {
+            // Without words, a word starts at each kanji run and keeps the kana after it
+            let mut romaji_text = String::new();
+            for segment in align(jp_text, jp_kana) {
+                let segment_romaji = romaji(segment.reading.as_deref().unwrap_or(&segment.text), reading_format);
+                if segment.reading.is_some() && !romaji_text.ends_with(' ') && !segment_romaji.starts_with(['.', ',', '!', '?', ' ', '"']) {
+                    romaji_text.push(' ');
+                }
+                romaji_text.push_str(&segment_romaji);
+            }
+            romaji_text.trim().to_string()
+        },
+    }
+}
+
+// Kanji are swapped for their reading, kana are kept as written
+fn hiragana_reading(jp_text: &str, jp_kana: &str) -> String {
+    align(jp_text, jp_kana).into_iter().map(|segment| segment.reading.unwrap_or(segment.text)).collect()
+}
+
+// Kanji runs with their readings, taken word by word when the line was tokenized
+pub fn segments(jp_text: &str, jp_kana: &str, words: &[Word], known_words: &HashSet<String>) -> Vec<Segment> {
+    if words.is_empty() {
+        return align(jp_text, jp_kana);
+    }
+    words.iter().flat_map(|word| {
+        if word.is_known(known_words) {
+            vec![Segment { text: word.surface.clone(), reading: None }]
+        } else {
+            align(&word.surface, &word.reading)
+        }
+    }).collect()
+}
+
+// The reading of one kanji word, for ruby above it
+pub fn ruby_reading(kana_text: &str, reading_format: i32) -> String {
+    match reading_format {
+        HIRAGANA | KANA => kana_text.chars().map(to_hiragana).collect(),
+        KATAKANA => kana_text.chars().map(to_katakana).collect(),
+        _ => romaji(kana_text, reading_format),
+    }
+}
+
+// ________________________________________
+// Map the hiragana reading back onto the
+// kanji runs of the text. Kana around the
+// kanji anchor where each reading stops
+// ----------------------------------------
+pub fn align(jp_text: &str, jp_kana: &str) -> Vec<Segment> {
+    let text: Vec<char> = jp_text.chars().collect();
+    let kana: Vec<char> = jp_kana.chars().map(to_hiragana).collect();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut k = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if !is_kanji(text[i]) {
+            if k < kana.len() && to_hiragana(text[i]) == kana[k] {
+                k += 1;
+            }
+            match segments.last_mut() {
+                Some(segment) if segment.reading.is_none() => segment.text.push(text[i]),
+                _ => segments.push(Segment { text: text[i].to_string(), reading: None }),
+            }
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < text.len() && is_kanji(text[i]) {
+            i += 1;
+        }
+        let kanji_run: String = text[start..i].iter().collect();
+        // The reading runs up to the next kana of the text, at least one kana per run
+        let end = match text.get(i).map(|c| to_hiragana(*c)) {
+            Some(anchor) => (k + 1..kana.len()).find(|j| kana[*j] == anchor),
+            None => Some(kana.len()),
+        };
+        match end {
+            Some(end) if end > k => {
+                segments.push(Segment { text: kanji_run, reading: Some(kana[k..end].iter().collect()) });
+                k = end;
+            },
+            // Reading and text went out of step, the rest is left unread
+            _ => {
+                segments.push(Segment { text: kanji_run, reading: None });
+                k = kana.len();
+            },
+        }
+    }
+    segments
+}
+
+// ________________________________________
+// Kana to romaji in Hepburn, Kunrei-shiki
+// or Nihon-shiki
+// ----------------------------------------
+pub fn romaji(kana_text: &str, reading_format: i32) -> String {
+    let chars: Vec<char> = kana_text.chars().map(to_hiragana).collect();
+    let mut romaji_text = String::new();
+    let mut is_geminate = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let mut syllable = match (syllable(c, reading_format), next) {
+            // Contracted sounds like きゃ or しゅ
+            (Some(stem), Some(small)) if stem.ends_with('i') && stem.len() > 1 && matches!(small, 'ゃ' | 'ゅ' | 'ょ') => {
+                i += 1;
+                let vowel = match small { 'ゃ' => "a", 'ゅ' => "u", _ => "o" };
+                let stem = &stem[..stem.len() - 1];
+                if stem.ends_with("sh") || stem.ends_with("ch") || stem.ends_with('j') {
+                    format!("{}{}", stem, vowel)
+                } else {
+                    format!("{}y{}", stem, vowel)
+                }
+            },
+            (Some(syllable), _) => syllable.to_string(),
+            (None, _) => match c {
+                'っ' => {
+                    is_geminate = true;
+                    i += 1;
+                    continue;
+                },
+                'ん' => {
+                    let is_ambiguous = next.and_then(|n| syllable(n, reading_format)).is_some_and(|n| n.starts_with(['a', 'i', 'u', 'e', 'o', 'y']));
+                    if is_ambiguous { "n'".to_string() } else { "n".to_string() }
+                },
+                // Long vowel mark repeats the vowel before it
+                'ー' => romaji_text.chars().rev().find(|v| "aiueo".contains(*v)).map(|v| v.to_string()).unwrap_or_default(),
+                '。' | '．' => ".".to_string(),
+                '、' | '，' => ",".to_string(),
+                '！' => "!".to_string(),
+                '？' => "?".to_string(),
+                '「' | '」' | '『' | '』' => "\"".to_string(),
+                '・' | '　' => " ".to_string(),
+                _ => c.to_string(),
+            },
+        };
+        if is_geminate {
+            is_geminate = false;
+            if reading_format == HEPBURN && syllable.starts_with("ch") {
+                syllable.insert(0, 't');
+            } else if let Some(consonant) = syllable.chars().next().filter(|c| c.is_ascii_alphabetic() && !"aiueon".contains(*c)) {
+                syllable.insert(0, consonant);
+            }
+        }
+        romaji_text.push_str(&syllable);
+        i += 1;
+    }
+    romaji_text
+}
+
+fn syllable(c: char, reading_format: i32) -> Option<&'static str> {
+    let syllable = match c {
+        'あ' | 'ぁ' => "a", 'い' | 'ぃ' => "i", 'う' | 'ぅ' => "u", 'え' | 'ぇ' => "e", 'お' | 'ぉ' => "o",
+        'か' => "ka", 'き' => "ki", 'く' => "ku", 'け' => "ke", 'こ' => "ko",
+        'が' => "ga", 'ぎ' => "gi", 'ぐ' => "gu", 'げ' => "ge", 'ご' => "go",
+        'さ' => "sa", 'す' => "su", 'せ' => "se", 'そ' => "so",
+        'ざ' => "za", 'ず' => "zu", 'ぜ' => "ze", 'ぞ' => "zo",
+        'た' => "ta", 'て' => "te", 'と' => "to",
+        'だ' => "da", 'で' => "de", 'ど' => "do",
+        'な' => "na", 'に' => "ni", 'ぬ' => "nu", 'ね' => "ne", 'の' => "no",
+        'は' => "ha", 'ひ' => "hi", 'へ' => "he", 'ほ' => "ho",
+        'ば' => "ba", 'び' => "bi", 'ぶ' => "bu", 'べ' => "be", 'ぼ' => "bo",
+        'ぱ' => "pa", 'ぴ' => "pi", 'ぷ' => "pu", 'ぺ' => "pe", 'ぽ' => "po",
+        'ま' => "ma", 'み' => "mi", 'む' => "mu", 'め' => "me", 'も' => "mo",
+        'や' | 'ゃ' => "ya", 'ゆ' | 'ゅ' => "yu", 'よ' | 'ょ' => "yo",
+        'ら' => "ra", 'り' => "ri", 'る' => "ru", 'れ' => "re", 'ろ' => "ro",
+        'わ' | 'ゎ' => "wa", 'ゔ' => "vu",
+        // Where the three systems part ways
+        'し' => if reading_format == HEPBURN { "shi" } else { "si" },
+        'ち' => if reading_format == HEPBURN { "chi" } else { "ti" },
+        'つ' => if reading_format == HEPBURN { "tsu" } else { "tu" },
+        'ふ' => if reading_format == HEPBURN { "fu" } else { "hu" },
+        'じ' => if reading_format == HEPBURN { "ji" } else { "zi" },
+        'ぢ' => match reading_format { HEPBURN => "ji", NIHON_SHIKI => "di", _ => "zi" },
+        'づ' => match reading_format { NIHON_SHIKI => "du", _ => "zu" },
+        'を' => if reading_format == NIHON_SHIKI { "wo" } else { "o" },
+        'ゐ' => if reading_format == NIHON_SHIKI { "wi" } else { "i" },
+        'ゑ' => if reading_format == NIHON_SHIKI { "we" } else { "e" },
+        _ => return None,
+    };
+    Some(syllable)
+}
+
+pub fn to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+pub fn to_katakana(c: char) -> char {
+    match c {
+        '\u{3041}'..='\u{3096}' => char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+pub fn is_kanji(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}' | '々' | 'ヶ' | '〆')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::PITCH_OVERLINE;
+
+    fn word(surface: &str, reading: &str) -> Word {
+        Word {
+            surface: surface.to_string(),
+            start: 0,
+            pos: "名詞".to_string(),
+            pos_detail: String::new(),
+            conjugation: String::new(),
+            base_form: surface.to_string(),
+            reading: reading.to_string(),
+        }
+    }
+
+    fn reading_of(words: &[Word], accents: &[Option<u32>], pitch_style: i32, reading_format: i32) -> String {
+        let jp_text: String = words.iter().map(|word| word.surface.as_str()).collect();
+        let jp_kana: String = words.iter().map(|word| word.reading.as_str()).collect();
+        format_reading(&jp_text, &jp_kana, words, &HashSet::new(), accents, pitch_style, reading_format)
+    }
+
+    #[test]
+    fn hiragana_keeps_kana_as_written() {
+        let words = [word("お茶", "おちゃ"), word("コーヒー", "こーひー")];
+        assert_eq!(reading_of(&words, &[None, None], PITCH_OFF, HIRAGANA), "おちゃコーヒー");
+        assert_eq!(reading_of(&words, &[Some(0), Some(3)], 1, HIRAGANA), "おちゃコーヒꜜー");
+        assert_eq!(reading_of(&words, &[Some(0), Some(3)], 1, KATAKANA), "オチャコーヒꜜー");
+    }
+
+    #[test]
+    fn accents_follow_the_reading_format() {
+        let words = [word("箸", "はし")];
+        assert_eq!(reading_of(&words, &[Some(1)], 1, HIRAGANA), "はꜜし");
+        assert_eq!(reading_of(&words, &[Some(1)], 1, HEPBURN), "haꜜshi");
+        assert_eq!(reading_of(&words, &[Some(1)], PITCH_OVERLINE, HIRAGANA), "は\u{0305}ꜜし");
+        assert_eq!(reading_of(&words, &[Some(1)], PITCH_OFF, HIRAGANA), "はし");
+    }
+}
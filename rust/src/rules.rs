@@ -0,0 +1,111 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    Ignore,
+    Replace,
+    Exact,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TextRule {
+    pub kind: RuleKind,
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+pub enum RuleOutcome {
+    Kept(String),
+    // Index of the rule that dropped the text
+    Ignored(usize),
+}
+
+// ============================================================
+// -- RuleSet --
+// Per-game rules run over every paragraph before translation,
+// written one per line in the Settings tab:
+//   ignore <regex>
+//   replace <regex> => <replacement>
+//   exact <text>
+// ============================================================
+pub struct RuleSet {
+    rules: Vec<(TextRule, Option<Regex>)>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<TextRule>) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for (i, rule) in rules.into_iter().enumerate() {
+            let regex = match rule.kind {
+                RuleKind::Exact => None,
+                _ => Some(Regex::new(&rule.pattern).map_err(|e| format!("Rule {} is not a valid regex: {}", i + 1, e))?),
+            };
+            compiled.push((rule, regex));
+        }
+        Ok(RuleSet { rules: compiled })
+    }
+
+    pub fn apply(&self, text: &str) -> RuleOutcome {
+        let mut rewritten = text.to_string();
+        for (i, (rule, regex)) in self.rules.iter().enumerate() {
+            match (rule.kind, regex) {
+                (RuleKind::Exact, _) if rewritten.trim() == rule.pattern.trim() => return RuleOutcome::Ignored(i),
+                (RuleKind::Ignore, Some(regex)) if regex.is_match(&rewritten) => return RuleOutcome::Ignored(i),
+                (RuleKind::Replace, Some(regex)) => {
+                    rewritten = regex.replace_all(&rewritten, rule.replacement.as_str()).into_owned();
+                    // A paragraph rewritten down to nothing has nothing left to translate
+                    if rewritten.trim().is_empty() {
+                        return RuleOutcome::Ignored(i);
+                    }
+                },
+                _ => {},
+            }
+        }
+        RuleOutcome::Kept(rewritten)
+    }
+}
+
+pub fn parse_rules(rules_text: &str) -> Result<Vec<TextRule>, String> {
+    let mut rules = Vec::new();
+    for (i, line) in rules_text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let rule = match keyword {
+            "ignore" => TextRule { kind: RuleKind::Ignore, pattern: rest.to_string(), replacement: String::new() },
+            "exact" => TextRule { kind: RuleKind::Exact, pattern: rest.to_string(), replacement: String::new() },
+            "replace" => match rest.split_once("=>") {
+                Some((pattern, replacement)) => TextRule {
+                    kind: RuleKind::Replace,
+                    pattern: pattern.trim().to_string(),
+                    replacement: replacement.trim().to_string(),
+                },
+                None => return Err(format!("Line {}: replace rules need a \"=>\" between pattern and replacement", i + 1)),
+            },
+            _ => return Err(format!("Line {}: rules start with ignore, replace or exact", i + 1)),
+        };
+        if rule.pattern.is_empty() {
+            return Err(format!("Line {}: the rule has no pattern", i + 1));
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+pub fn format_rules(rules: &[TextRule]) -> String {
+    let mut lines = Vec::new();
+    for rule in rules {
+        match rule.kind {
+            RuleKind::Ignore => lines.push(format!("ignore {}", rule.pattern)),
+            RuleKind::Replace => lines.push(format!("replace {} => {}", rule.pattern, rule.replacement)),
+            RuleKind::Exact => lines.push(format!("exact {}", rule.pattern)),
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,175 @@
+use serde::{Serialize, Deserialize};
+use crate::ocr::{self, Extent, OcrParagraph, OcrSymbol, Point};
+
+// Characters that close a line of dialogue
+const SENTENCE_ENDINGS: [char; 10] = ['。', '！', '？', '」', '』', '!', '?', '．', '…', '♪'];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SegmentConfig {
+    pub merge: bool,
+    pub merge_distance: f32,
+    pub split: bool,
+    pub split_gap: f32,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        SegmentConfig {
+            merge: true,
+            merge_distance: 1.0,
+            split: true,
+            split_gap: 1.5,
+        }
+    }
+}
+
+// ________________________________________
+// Turn OCR paragraphs (already in reading
+// order) into dialogue units: split where
+// lines are far apart, then merge lines
+// of one sentence that OCR tore apart
+// ----------------------------------------
+pub fn segment(paragraphs: Vec<OcrParagraph>, config: &SegmentConfig) -> Vec<OcrParagraph> {
+    let mut units = Vec::new();
+    for paragraph in paragraphs {
+        if config.split {
+            units.extend(split_paragraph(paragraph, config.split_gap));
+        } else {
+            units.push(paragraph);
+        }
+    }
+    if !config.merge {
+        return units;
+    }
+    let mut merged: Vec<OcrParagraph> = Vec::new();
+    for unit in units {
+        if let Some(previous) = merged.last_mut() {
+            if should_merge(previous, &unit, config.merge_distance) {
+                merge_into(previous, unit);
+                continue;
+            }
+        }
+        merged.push(unit);
+    }
+    merged
+}
+
+fn split_paragraph(paragraph: OcrParagraph, split_gap: f32) -> Vec<OcrParagraph> {
+    let is_vertical = paragraph.is_vertical;
+    let furigana = paragraph.furigana;
+    let lines = group_lines(paragraph.symbols);
+    let mut units: Vec<OcrParagraph> = Vec::new();
+    let mut previous_extent: Option<Extent> = None;
+    for line in lines {
+        let line_extent = symbols_extent(&line);
+        let is_gap = match previous_extent {
+            Some(previous_extent) => {
+                cross_gap(previous_extent, line_extent, is_vertical) > split_gap * thickness(previous_extent, is_vertical)
+            },
+            None => true,
+        };
+        match units.last_mut() {
+            Some(unit) if !is_gap => {
+                unit.symbols.extend(line);
+                unit.bounds = ocr::to_polygon(ocr::union(ocr::extent(&unit.bounds), line_extent));
+            },
+            _ => units.push(OcrParagraph {
+                symbols: line,
+                bounds: ocr::to_polygon(line_extent),
+                is_vertical,
+                furigana: Vec::new(),
+            }),
+        }
+        previous_extent = Some(line_extent);
+    }
+    // Ruby goes with the unit it sits closest to
+    for ruby in furigana {
+        let ruby_center = ocr::center(&ruby.bounds);
+        let closest_unit = units.iter_mut().min_by(|a, b| {
+            distance(ocr::center(&a.bounds), ruby_center).total_cmp(&distance(ocr::center(&b.bounds), ruby_center))
+        });
+        if let Some(unit) = closest_unit {
+            unit.furigana.push(ruby);
+        }
+    }
+    units
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+// A line ends wherever Vision detected a line break
+fn group_lines(symbols: Vec<OcrSymbol>) -> Vec<Vec<OcrSymbol>> {
+    let mut lines = vec![Vec::new()];
+    for symbol in symbols {
+        let is_line_end = matches!(symbol.break_type.as_deref(), Some("LINE_BREAK") | Some("EOL_SURE_SPACE"));
+        lines.last_mut().unwrap().push(symbol);
+        if is_line_end {
+            lines.push(Vec::new());
+        }
+    }
+    lines.retain(|line| !line.is_empty());
+    lines
+}
+
+fn should_merge(previous: &OcrParagraph, next: &OcrParagraph, merge_distance: f32) -> bool {
+    if previous.is_vertical != next.is_vertical {
+        return false;
+    }
+    let previous_text = previous.text();
+    if previous_text.trim_end().ends_with(SENTENCE_ENDINGS) {
+        return false;
+    }
+    let previous_extent = ocr::extent(&previous.bounds);
+    let next_extent = ocr::extent(&next.bounds);
+    let is_vertical = previous.is_vertical;
+    // The next unit has to continue in the same column (or row) span
+    let is_aligned = if is_vertical {
+        previous_extent.min_y < next_extent.max_y && next_extent.min_y < previous_extent.max_y
+    } else {
+        previous_extent.min_x < next_extent.max_x && next_extent.min_x < previous_extent.max_x
+    };
+    let line_thickness = match previous.symbols.last() {
+        Some(symbol) => thickness(ocr::extent(&symbol.bounds), is_vertical),
+        None => thickness(previous_extent, is_vertical),
+    };
+    is_aligned && cross_gap(previous_extent, next_extent, is_vertical) <= merge_distance * line_thickness
+}
+
+fn merge_into(previous: &mut OcrParagraph, next: OcrParagraph) {
+    if let Some(symbol) = previous.symbols.last_mut() {
+        if symbol.break_type.is_none() {
+            symbol.break_type = Some("LINE_BREAK".to_string());
+        }
+    }
+    previous.bounds = ocr::to_polygon(ocr::union(ocr::extent(&previous.bounds), ocr::extent(&next.bounds)));
+    previous.symbols.extend(next.symbols);
+    previous.furigana.extend(next.furigana);
+}
+
+// Empty space between two boxes across the writing direction.
+// Vertical lines follow each other to the left, horizontal ones downwards
+fn cross_gap(previous: Extent, next: Extent, is_vertical: bool) -> f32 {
+    if is_vertical {
+        (previous.min_x - next.max_x).max(next.min_x - previous.max_x)
+    } else {
+        (next.min_y - previous.max_y).max(previous.min_y - next.max_y)
+    }
+}
+
+fn thickness(line_extent: Extent, is_vertical: bool) -> f32 {
+    if is_vertical {
+        line_extent.max_x - line_extent.min_x
+    } else {
+        line_extent.max_y - line_extent.min_y
+    }
+}
+
+fn symbols_extent(symbols: &[OcrSymbol]) -> Extent {
+    let mut symbols_extent = ocr::extent(&symbols[0].bounds);
+    for symbol in &symbols[1..] {
+        symbols_extent = ocr::union(symbols_extent, ocr::extent(&symbol.bounds));
+    }
+    symbols_extent
+}
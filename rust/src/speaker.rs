@@ -0,0 +1,203 @@
+use serde::{Serialize, Deserialize};
+use crate::ocr::{self, Point};
+
+// Longest name accepted in front of 「 or ：, anything longer is dialogue
+const MAX_NAME_LENGTH: usize = 12;
+// Characters a name never contains: sentence punctuation and spaces
+const NOT_IN_NAME: [char; 9] = ['。', '、', '！', '？', '」', '!', '?', ' ', '　'];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpeakerConfig {
+    pub patterns: bool,
+    // Name plate as percentages of the capture: left, top, right, bottom
+    pub name_region: Option<[f32; 4]>,
+}
+
+impl Default for SpeakerConfig {
+    fn default() -> Self {
+        SpeakerConfig {
+            patterns: true,
+            name_region: None,
+        }
+    }
+}
+
+// A known name and its translation, which is filled in the first time
+// the name is read and reused from then on
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CastMember {
+    pub name: String,
+    #[serde(default)]
+    pub translation: String,
+}
+
+#[derive(Clone)]
+pub struct SpeakerName {
+    pub jp: String,
+    pub eng: String,
+}
+
+// ________________________________________
+// Split "【Name】line", "Name「line」",
+// "Name：line" or a cast name at the start
+// of the text into speaker and dialogue
+// ----------------------------------------
+pub fn split_speaker(text: &str, first_line: &str, cast: &[CastMember], patterns: bool) -> (Option<String>, String) {
+    let text = text.trim();
+    let first_line = first_line.trim();
+    if patterns {
+        for (open, close) in [('【', '】'), ('［', '］'), ('[', ']')] {
+            if let Some(rest) = text.strip_prefix(open) {
+                if let Some((name, line)) = rest.split_once(close) {
+                    if is_name(name) {
+                        return (Some(name.trim().to_string()), line.trim().to_string());
+                    }
+                }
+            }
+        }
+        // Name「line」 only when the quote closes the text, so a quoted
+        // word inside a sentence is not taken for a name
+        for (open, close) in [('「', '」'), ('『', '』')] {
+            if let Some((name, line)) = text.split_once(open) {
+                if is_name(name) && text.ends_with(close) {
+                    return (Some(name.trim().to_string()), format!("{}{}", open, line.trim()));
+                }
+            }
+        }
+        // Name：line only when the line is Japanese, so "HP:100" stays text
+        for separator in ['：', ':'] {
+            if let Some((name, line)) = text.split_once(separator) {
+                if is_name(name) && has_japanese(line) {
+                    return (Some(name.trim().to_string()), line.trim().to_string());
+                }
+            }
+        }
+    }
+    // Longest names first, so "Taro" does not shadow "Taro's father"
+    let mut cast_names: Vec<&str> = cast.iter().map(|member| member.name.as_str()).collect();
+    cast_names.sort_by_key(|name| std::cmp::Reverse(name.chars().count()));
+    for name in cast_names {
+        if let Some(rest) = text.strip_prefix(name) {
+            let line = rest.trim_start();
+            // Japanese lines are joined without a space, so a name plate
+            // read into the paragraph shows as a first line of its own,
+            // or is followed by a line break or space
+            let is_name_plate = first_line == name || rest.starts_with(char::is_whitespace);
+            if line.starts_with(['「', '『', '（', '：', ':']) || is_name_plate {
+                let line = line.trim_start_matches(['：', ':']).trim();
+                return (Some(name.to_string()), line.to_string());
+            }
+        }
+    }
+    (None, text.to_string())
+}
+
+// A name is 1 to 12 characters with at least one letter, no digits and no
+// sentence punctuation, so "12:30" or "残り3：" are not names
+fn is_name(name: &str) -> bool {
+    let name = name.trim();
+    let length = name.chars().count();
+    length > 0 && length <= MAX_NAME_LENGTH
+        && name.chars().any(char::is_alphabetic)
+        && !name.chars().any(|c| c.is_numeric())
+        && !name.contains(NOT_IN_NAME)
+}
+
+// Kana or kanji, which every line of dialogue has
+fn has_japanese(text: &str) -> bool {
+    text.chars().any(|c| matches!(c, '\u{3040}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '々'))
+}
+
+pub fn in_name_region(bounds: &[Point], name_region: [f32; 4], capture_size: (u32, u32)) -> bool {
+    let center = ocr::center(bounds);
+    let x = center.x / capture_size.0.max(1) as f32 * 100.0;
+    let y = center.y / capture_size.1.max(1) as f32 * 100.0;
+    x >= name_region[0] && y >= name_region[1] && x <= name_region[2] && y <= name_region[3]
+}
+
+pub fn parse_name_region(region_text: &str) -> Option<[f32; 4]> {
+    let values: Vec<f32> = region_text.split(',').filter_map(|value| value.trim().parse::<f32>().ok()).collect();
+    match values[..] {
+        [left, top, right, bottom] if left < right && top < bottom => Some([left, top, right, bottom]),
+        _ => None,
+    }
+}
+
+pub fn format_name_region(name_region: Option<[f32; 4]>) -> String {
+    match name_region {
+        Some([left, top, right, bottom]) => format!("{},{},{},{}", left, top, right, bottom),
+        None => String::new(),
+    }
+}
+
+// One name per line, with an optional "= translation"
+pub fn parse_cast(cast_text: &str) -> Vec<CastMember> {
+    let mut cast = Vec::new();
+    for line in cast_text.lines() {
+        let (name, translation) = line.split_once('=').unwrap_or((line, ""));
+        if !name.trim().is_empty() {
+            cast.push(CastMember {
+                name: name.trim().to_string(),
+                translation: translation.trim().to_string(),
+            });
+        }
+    }
+    cast
+}
+
+pub fn format_cast(cast: &[CastMember]) -> String {
+    let mut lines = Vec::new();
+    for member in cast {
+        if member.translation.is_empty() {
+            lines.push(member.name.clone());
+        } else {
+            lines.push(format!("{} = {}", member.name, member.translation));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cast(names: &[&str]) -> Vec<CastMember> {
+        names.iter().map(|name| CastMember { name: name.to_string(), translation: String::new() }).collect()
+    }
+
+    fn speaker_of(text: &str, cast: &[CastMember]) -> (Option<String>, String) {
+        let first_line = text.lines().next().unwrap_or("");
+        split_speaker(text, first_line, cast, true)
+    }
+
+    #[test]
+    fn bracket_and_quote_names() {
+        assert_eq!(speaker_of("【アリス】行くよ", &[]), (Some("アリス".to_string()), "行くよ".to_string()));
+        assert_eq!(speaker_of("アリス「行くよ」", &[]), (Some("アリス".to_string()), "「行くよ」".to_string()));
+        // A quoted word inside a sentence is not a name
+        assert_eq!(speaker_of("彼は「行く」と言った", &[]).0, None);
+    }
+
+    #[test]
+    fn separator_names() {
+        assert_eq!(speaker_of("アリス：行くよ", &[]), (Some("アリス".to_string()), "行くよ".to_string()));
+        assert_eq!(speaker_of("Alice: 行くよ", &[]).0, Some("Alice".to_string()));
+    }
+
+    #[test]
+    fn status_lines_and_times_are_not_speakers() {
+        assert_eq!(speaker_of("HP:100", &[]), (None, "HP:100".to_string()));
+        assert_eq!(speaker_of("ＨＰ：100/120", &[]).0, None);
+        assert_eq!(speaker_of("12:30", &[]).0, None);
+        assert_eq!(speaker_of("残り3：あと少し", &[]).0, None);
+    }
+
+    #[test]
+    fn cast_names() {
+        let cast = cast(&["アリス", "アリスの父"]);
+        assert_eq!(speaker_of("アリス\n行くよ", &cast), (Some("アリス".to_string()), "行くよ".to_string()));
+        assert_eq!(speaker_of("アリスの父「待て」", &cast).0, Some("アリスの父".to_string()));
+        // The name is part of the sentence
+        assert_eq!(speaker_of("アリスの家に行く", &cast).0, None);
+    }
+}
@@ -3,22 +3,67 @@ use std::thread;
 use std::time::Duration;
 use std::io::Cursor;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::collections::{HashMap, HashSet};
 use godot::prelude::*;
-use godot::classes::{InputEvent, Control, PanelContainer, VBoxContainer, HBoxContainer, Image, ImageTexture, TextureRect, LineEdit, TextEdit, RichTextLabel, FileAccess, OptionButton, CheckButton, Font};
+use godot::classes::{InputEvent, Control, PanelContainer, VBoxContainer, HBoxContainer, Image, ImageTexture, TextureRect, LineEdit, TextEdit, RichTextLabel, FileAccess, OptionButton, CheckButton, HFlowContainer, Label, Font, FileDialog, DisplayServer, TabContainer, Button, Time, AudioStreamPlayer, AudioStreamWav};
 use godot::classes::file_access::ModeFlags;
+use godot::classes::control::SizeFlags;
+use godot::classes::text_server::AutowrapMode;
+use godot::classes::audio_stream_wav::Format;
+use godot::global::HorizontalAlignment;
 use xcap::Window;
-use image::{ImageBuffer, Rgba, ImageOutputFormat, GenericImageView, DynamicImage};
-use base64::encode;
+use image::{ImageBuffer, Rgba, RgbaImage, ImageOutputFormat, GenericImageView, DynamicImage};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
 use reqwest;
 use kakasi::IsJapanese;
 use crate::utils::*;
-use crate::gui::sandGUI;
+use crate::gui::{sandGUI, sandOverlay};
+use crate::ocr::{self, Point};
+use crate::capture::{self, AutoCapture, Portal};
+use crate::preprocess::{self, PreprocessConfig};
+use crate::history::CaptureHistory;
+use crate::segment::{self, SegmentConfig};
+use crate::furigana::{self, FuriganaConfig};
+use crate::rules::{self, RuleSet, RuleOutcome, TextRule};
+use crate::normalize::{self, NormalizeConfig};
+use crate::speaker::{self, SpeakerConfig, CastMember, SpeakerName};
+use crate::reading;
+use crate::tokenizer::{self, Tokenizer, Word};
+use crate::dictionary::{self, Jmdict};
+use crate::kanji::{self, Kanjidic};
+use crate::difficulty::{self, HighlightConfig};
+use crate::anki::{self, AnkiConfig, FieldMapping, MinedNote, MinedWord};
+use crate::tts::{self, SpeechConfig};
+use crate::pitch::{self, AccentDictionary};
+
+const JMDICT_PATH: &str = "res://dict/jmdict-eng.json";
+const KANJIDIC_PATH: &str = "res://dict/kanjidic2-en.json";
+const JLPT_PATH: &str = "res://dict/jlpt.tsv";
+const FREQUENCY_PATH: &str = "res://dict/frequency.tsv";
+const ACCENTS_PATH: &str = "res://dict/accents.tsv";
+
+// Confidences used when their fields are blank, or missing from older settings
+const DEFAULT_MIN_CONFIDENCE: f32 = 0.5;
+const DEFAULT_MARK_CONFIDENCE: f32 = 0.8;
 
 struct ScreenCapture {
+    // Preprocessed for OCR
     png_buffer: Cursor<Vec<u8>>,
+    // The crop as captured, kept in the history
+    crop_png_bytes: Vec<u8>,
     is_preview: bool,
+    fingerprint: Vec<u8>,
+    // Set when a capture from the history is read again
+    history_id: Option<u64>,
+}
+
+// An auto capture sample, only preprocessed once its text has settled
+struct Sample {
+    crop: RgbaImage,
+    fingerprint: Vec<u8>,
 }
 
 struct ErrorOrWarning {
@@ -26,17 +71,65 @@ struct ErrorOrWarning {
     is_warning: bool,
 }
 
+#[derive(Clone)]
 struct TranslationPacket {
     jp_text: String,
+    // Hiragana, shown in the reading format picked in the Settings tab
     jp_read: String,
     eng_text: String,
+    bounds: Vec<Point>,
+    confidences: Vec<f32>,
+    speaker: Option<SpeakerName>,
+    // Empty when the tokenizer is not available
+    words: Vec<Word>,
 }
 
-#[derive(Serialize, Deserialize)]
+impl TranslationPacket {
+    // Packets with the same key show the same line and need no new translation
+    fn key(&self) -> String {
+        packet_key(self.speaker.as_ref().map(|speaker_name| speaker_name.jp.as_str()), &self.jp_text)
+    }
+}
+
+fn packet_key(speaker_jp: Option<&str>, jp_text: &str) -> String {
+    format!("{}\n{}", speaker_jp.unwrap_or(""), jp_text.trim())
+}
+
+// Everything the reading thread needs to turn OCR into packets
+struct ReadingConfig {
+    segment: SegmentConfig,
+    furigana: FuriganaConfig,
+    normalize: NormalizeConfig,
+    min_confidence: f32,
+    rule_set: RuleSet,
+    speaker: SpeakerConfig,
+    cast: Vec<CastMember>,
+    capture_size: (u32, u32),
+    upscale: u32,
+    previous_packets: Vec<TranslationPacket>,
+    tokenizer: Option<Arc<Tokenizer>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
 struct UserSettings {
     user_credentials: Option<UserCredentials>,
     reading_area: Option<ReadingArea>,
     packet_config: Option<PacketConfig>,
+    auto_capture: Option<AutoCaptureConfig>,
+    preprocessing: Option<PreprocessConfig>,
+    wayland_portal: Option<WaylandPortal>,
+    segmentation: Option<SegmentConfig>,
+    furigana: Option<FuriganaConfig>,
+    normalization: Option<NormalizeConfig>,
+    speakers: Option<SpeakerConfig>,
+    // Keyed by the game profile name
+    game_profile: Option<String>,
+    text_rules: Option<HashMap<String, Vec<TextRule>>>,
+    casts: Option<HashMap<String, Vec<CastMember>>>,
+    // Dictionary forms, sorted
+    known_words: Option<Vec<String>>,
+    anki: Option<AnkiConfig>,
+    speech: Option<SpeechConfig>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -59,6 +152,43 @@ struct PacketConfig {
     jp_font: i32,
     font_size: usize,
     romaji: bool,
+    #[serde(default = "default_min_confidence")]
+    min_confidence: f32,
+    #[serde(default = "default_mark_confidence")]
+    mark_confidence: f32,
+    #[serde(default)]
+    reading_format: i32,
+    #[serde(default)]
+    ruby: bool,
+    #[serde(default)]
+    highlight_mode: i32,
+    #[serde(default)]
+    highlight_style: i32,
+    #[serde(default)]
+    known_jlpt: i32,
+    #[serde(default)]
+    known_frequency: u32,
+    #[serde(default)]
+    pitch_accent: i32,
+}
+
+fn default_min_confidence() -> f32 {
+    DEFAULT_MIN_CONFIDENCE
+}
+
+fn default_mark_confidence() -> f32 {
+    DEFAULT_MARK_CONFIDENCE
+}
+
+#[derive(Serialize, Deserialize)]
+struct AutoCaptureConfig {
+    enabled: bool,
+    interval: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WaylandPortal {
+    restore_token: String,
 }
 
 enum SystemState {
@@ -80,6 +210,37 @@ pub struct System {
     packets_queue: Arc<Mutex<Vec<Vec<TranslationPacket>>>>,
     error_queue: Arc<Mutex<Vec<ErrorOrWarning>>>,
     box_queue: Arc<Mutex<Vec<()>>>,
+    auto_capture: AutoCapture,
+    sample_queue: Arc<Mutex<Vec<Sample>>>,
+    history: CaptureHistory,
+    history_save_id: Option<u64>,
+    portal: Arc<Mutex<Portal>>,
+    saved_portal_token: Option<String>,
+    packet_nodes: Vec<Gd<PanelContainer>>,
+    packet_keys: Vec<String>,
+    previous_packets: Vec<TranslationPacket>,
+    highlighted_packet: Option<usize>,
+    last_response: Arc<Mutex<Option<Value>>>,
+    // Upscale of the last capture sent to OCR, to bring its bounds back to the crop
+    last_upscale: u32,
+    glossary_queue: Arc<Mutex<Vec<CastMember>>>,
+    tokenizer: Option<Arc<Tokenizer>>,
+    jmdict: Arc<Mutex<Option<Jmdict>>>,
+    kanjidic: Arc<Mutex<Option<Kanjidic>>>,
+    jlpt_list: Arc<Mutex<Option<HashMap<String, u32>>>>,
+    frequency_list: Arc<Mutex<Option<HashMap<String, u32>>>>,
+    accent_dictionary: Arc<Mutex<Option<AccentDictionary>>>,
+    popup_pinned: bool,
+    // Dictionary form of the word in the popup, for the known words button
+    popup_word: Option<String>,
+    known_words: HashSet<String>,
+    // Capture being read, and the one the packets on screen came from
+    reading_capture_id: Option<u64>,
+    packets_capture_id: Option<u64>,
+    popup_packet: Option<usize>,
+    popup_mined_word: Option<MinedWord>,
+    anki_queue: Arc<Mutex<Vec<Result<String, String>>>>,
+    speech_queue: Arc<Mutex<Vec<Result<Vec<u8>, String>>>>,
 }
 
 #[godot_api]
@@ -94,6 +255,34 @@ impl INode for System {
             packets_queue: Arc::new(Mutex::new(Vec::new())),
             error_queue: Arc::new(Mutex::new(Vec::new())),
             box_queue: Arc::new(Mutex::new(Vec::new())),
+            auto_capture: AutoCapture::new(),
+            sample_queue: Arc::new(Mutex::new(Vec::new())),
+            history: CaptureHistory::new(),
+            history_save_id: None,
+            portal: Arc::new(Mutex::new(Portal::default())),
+            saved_portal_token: None,
+            packet_nodes: Vec::new(),
+            packet_keys: Vec::new(),
+            previous_packets: Vec::new(),
+            highlighted_packet: None,
+            last_response: Arc::new(Mutex::new(None)),
+            last_upscale: 1,
+            glossary_queue: Arc::new(Mutex::new(Vec::new())),
+            tokenizer: None,
+            jmdict: Arc::new(Mutex::new(None)),
+            kanjidic: Arc::new(Mutex::new(None)),
+            jlpt_list: Arc::new(Mutex::new(None)),
+            frequency_list: Arc::new(Mutex::new(None)),
+            accent_dictionary: Arc::new(Mutex::new(None)),
+            popup_pinned: false,
+            popup_word: None,
+            known_words: HashSet::new(),
+            reading_capture_id: None,
+            packets_capture_id: None,
+            popup_packet: None,
+            popup_mined_word: None,
+            anki_queue: Arc::new(Mutex::new(Vec::new())),
+            speech_queue: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -106,8 +295,30 @@ impl INode for System {
         if FileAccess::file_exists("user://user_settings.toml") {
             self.load_user_settings();
         }
+        // -- TOKENIZER --
+        match Tokenizer::new() {
+            Ok(tokenizer) => self.tokenizer = Some(Arc::new(tokenizer)),
+            Err(error) => self.log_warning(format!("{} Readings will come from KAKASI instead.", error)),
+        }
+        // -- DICTIONARIES --
+        load_in_background(JMDICT_PATH, Jmdict::from_json, Arc::clone(&self.jmdict));
+        load_in_background(KANJIDIC_PATH, Kanjidic::from_json, Arc::clone(&self.kanjidic));
+        load_in_background(JLPT_PATH, difficulty::parse_jlpt, Arc::clone(&self.jlpt_list));
+        load_in_background(FREQUENCY_PATH, difficulty::parse_frequency, Arc::clone(&self.frequency_list));
+        load_in_background(ACCENTS_PATH, pitch::parse_accents, Arc::clone(&self.accent_dictionary));
+        self.warn_missing_data();
         self.refresh_preview_packet();
+        self.refresh_segmentation_preview();
+        self.refresh_normalization_preview();
         self.list_windows();
+        self.load_game_settings();
+        // -- DRAG AND DROP --
+        let mut root = self.base().get_tree().unwrap().get_root().unwrap();
+        root.connect("files_dropped", &Callable::from_object_method(&self.to_gd(), "read_dropped_files"));
+    }
+
+    fn exit_tree(&mut self) {
+        self.portal.lock().unwrap().close();
     }
 
     fn input(&mut self, event: Gd<InputEvent>) {
@@ -117,6 +328,7 @@ impl INode for System {
         let is_mouse_right_clicked = Input::singleton().is_action_just_pressed("mouse_rightclick");
         let is_mouse_right_released = Input::singleton().is_action_just_released("mouse_rightclick");
         let is_capture_pressed = Input::singleton().is_action_just_pressed("capture");
+        let is_paste_pressed = Input::singleton().is_action_just_pressed("paste");
         if is_mouse_clicked {
             let mouse_cursor = load::<Resource>("res://menu/sprite/mouse_cursor_2.png");
             Input::singleton().set_custom_mouse_cursor(&mouse_cursor);
@@ -133,16 +345,59 @@ impl INode for System {
             let mouse_cursor = load::<Resource>("res://menu/sprite/mouse_cursor.png");
             Input::singleton().set_custom_mouse_cursor(&mouse_cursor);
         }
+        if is_mouse_clicked {
+            let popup = self.base().get_node_as::<PanelContainer>("sandGUI/word_popup");
+            let mut kanji_panel = self.base().get_node_as::<PanelContainer>("sandGUI/kanji_panel");
+            let is_inside = |panel: &Gd<PanelContainer>| panel.is_visible() && panel.get_global_rect().contains_point(panel.get_global_mouse_position());
+            if !is_inside(&popup) && !is_inside(&kanji_panel) {
+                if self.popup_pinned {
+                    self.hide_word_popup();
+                }
+                kanji_panel.set_visible(false);
+            }
+        }
         if is_capture_pressed {
             self.capture_screen(false);
         }
+        // Ctrl+V in a text field pastes text, not a screenshot
+        if is_paste_pressed && !self.is_typing() {
+            self.paste_clipboard_image();
+        }
     }
 
     fn process(&mut self, delta: f64) {
         let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
+        // ---- REGION HIGHLIGHT ----
+        self.highlight_regions();
+        // ---- ANKI ----
+        let anki_result = self.anki_queue.lock().unwrap().pop();
+        match anki_result {
+            Some(Ok(message)) => {
+                self.clear_errors();
+                console.set_text(&message);
+            },
+            Some(Err(error)) => self.log_error(error),
+            None => {},
+        }
+        // ---- SPEECH ----
+        // Only the newest line is played, lines asked for before it have been skipped over
+        let mut speech_results: Vec<_> = self.speech_queue.lock().unwrap().drain(..).collect();
+        let speech_result = speech_results.pop();
+        for skipped_result in speech_results {
+            if let Err(error) = skipped_result {
+                self.log_error(error);
+            }
+        }
+        match speech_result {
+            Some(Ok(wav_bytes)) => self.play_speech(&wav_bytes),
+            Some(Err(error)) => self.log_error(error),
+            None => {},
+        }
         match self.system_state {
             SystemState::IDLE => {
                 self.time_accumulator = 0.0;
+                // ---- AUTO CAPTURE ----
+                self.auto_capture_tick(delta as f32);
             },
             SystemState::CAPTURING => {
                 // ---- CONSOLE UPDATES ----
@@ -160,18 +415,27 @@ impl INode for System {
                 // ---- CATCH SCREEN CAPTURE ----
                 let mut screen_queue = self.screen_queue.lock().unwrap();
                 if let Some(screen_capture) = screen_queue.pop() {
+                    drop(screen_queue);
+                    self.save_portal_token();
                     let png_buffer = screen_capture.png_buffer;
-                    let mut screen_image = Image::new_gd();
-                    screen_image.load_png_from_buffer(&PackedByteArray::from(png_buffer.clone().into_inner().as_slice()));
-                    let screen_texture = ImageTexture::create_from_image(&screen_image).expect("Failed to create ImageTexture!");
-                    let mut screen_textrect = self.base().get_node_as::<TextureRect>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/PanelContainer/VBoxContainer/screen_textrect");
-                    screen_textrect.set_texture(&screen_texture);
+                    self.set_screen_preview(png_buffer.get_ref());
                     if screen_capture.is_preview == true {
                         console.set_text("Preview refreshed!");
                         self.system_state = SystemState::IDLE;
                     } else {
-                        drop(screen_queue);
-                        self.read_screen(png_buffer);
+                        self.auto_capture.mark_read(&screen_capture.fingerprint);
+                        // ---- CAPTURE HISTORY ----
+                        self.reading_capture_id = match screen_capture.history_id {
+                            Some(id) => Some(id),
+                            None => {
+                                let timestamp = Time::singleton().get_datetime_string_from_system_ex().use_space(true).done().to_string();
+                                let id = self.history.push(timestamp, screen_capture.crop_png_bytes);
+                                self.refresh_history();
+                                Some(id)
+                            }
+                        };
+                        // A re-read from the history is translated again, not served from the last packets
+                        self.read_screen(png_buffer, screen_capture.history_id.is_some());
                     }
                 }
                 // ---- CATCH ERRORS ----
@@ -204,11 +468,23 @@ impl INode for System {
                 if let Some(()) = box_queue.pop() {
                     self.add_box_to_conveyor();
                 }
+                drop(box_queue);
+                // ---- GLOSSARY ----
+                let mut glossary_queue = self.glossary_queue.lock().unwrap();
+                let new_names: Vec<CastMember> = glossary_queue.drain(..).collect();
+                drop(glossary_queue);
+                if !new_names.is_empty() {
+                    self.add_to_cast(new_names);
+                }
                 // ---- CATCH PACKETS ----
                 let mut gui = self.base().get_node_as::<sandGUI>("sandGUI");
                 let mut packets_queue = self.packets_queue.lock().unwrap();
                 if let Some(packets) = packets_queue.pop() {
+                    drop(packets_queue);
+                    self.packets_capture_id = self.reading_capture_id;
                     self.make_packets(gui, packets);
+                    self.refresh_segmentation_preview();
+                    self.refresh_normalization_preview();
                     console.set_text("Capturing Screen Done!\nReading Screen Done!");
                     self.open_conveyor_boxes();
                     self.system_state = SystemState::IDLE;
@@ -277,64 +553,211 @@ async fn send_deepl_api_request(text: &str, auth_key: &str) -> Result<Value, req
     Ok(response)
 }
 
-async fn parse_vision_response(response_json: Value, deepl_token: &str, box_queue_clone: Arc<Mutex<Vec<()>>>) -> Result<Vec<TranslationPacket>, ErrorOrWarning> {
+async fn parse_vision_response(response_json: Value, deepl_token: &str, reading_config: &ReadingConfig, glossary_queue_clone: Arc<Mutex<Vec<CastMember>>>, box_queue_clone: Arc<Mutex<Vec<()>>>) -> Result<Vec<TranslationPacket>, ErrorOrWarning> {
     let mut packets = Vec::new();
-    if let Some(pages) = response_json["responses"][0]["fullTextAnnotation"]["pages"].as_array() {
-        for page in pages {
-            if let Some(blocks) = page["blocks"].as_array() {
-                for block in blocks {
-                    if let Some(paragraphs) = block["paragraphs"].as_array() {
-                        for paragraph in paragraphs {
-                            let mut block_text = String::new();
-                            for word in paragraph["words"].as_array().unwrap() {
-                                for symbol in word["symbols"].as_array().unwrap() {
-                                    block_text.push_str(symbol["text"].as_str().unwrap());
-                                    if let Some(detected_break) = symbol["property"]["detectedBreak"].as_object() {
-                                        if detected_break.contains_key("type") {
-                                            block_text.push(' ');
-                                        }
-                                    }
-                                }
-                            }
-                            // ---- TRANSLATION PACKET PREP ----
-                            if kakasi::is_japanese(&block_text) == IsJapanese::False {
-                                continue;
-                            }
-                            // KAKASI
-                            let romaji_text = kakasi::convert(&block_text).romaji.into();
-                            // DEEPL TRANSLATION
-                            match send_deepl_api_request(&block_text, deepl_token).await {
-                                Ok(response) => {
-                                    godot_print!("DeepL response received!");
-                                    let translation_text = parse_deepl_response(response);
-                                    // WRAPUP
-                                    packets.push(TranslationPacket {
-                                        jp_text: block_text,
-                                        jp_read: romaji_text,
-                                        eng_text: translation_text,
-                                    });
-                                    let mut box_queue = box_queue_clone.lock().unwrap();
-                                    box_queue.push(());
-                                }
-                                Err(error) => {
-                                    godot_print!("DeepL error!");
-                                    let eow = ErrorOrWarning {
-                                        string: (format!("Failed to communicate with DeepL, check credentials!\n{}", error)),
-                                        is_warning: false,
-                                    };
-                                    return Err(eow);
-                                    break;
-                                }
-                            }
-                        }
-                    }
+    let mut glossary: HashMap<String, String> = reading_config.cast.iter()
+        .filter(|member| !member.translation.is_empty())
+        .map(|member| (member.name.clone(), member.translation.clone()))
+        .collect();
+    // A name plate is read as its own unit, right before its line
+    let mut plate_speaker: Option<String> = None;
+    for paragraph in read_units(&response_json, &reading_config.segment, &reading_config.furigana, reading_config.upscale) {
+        // ---- TRANSLATION PACKET PREP ----
+        if paragraph.confidence() < reading_config.min_confidence {
+            // A name plate belongs to the line right after it, not to one further down
+            plate_speaker = None;
+            continue;
+        }
+        let mut confidences = paragraph.char_confidences();
+        let (mut normalized_text, _) = normalize::normalize(&paragraph.text(), &reading_config.normalize);
+        // SPEAKER
+        let (first_line, _) = normalize::normalize(&paragraph.first_line(), &reading_config.normalize);
+        // Segmentation may have merged the name plate into the line below it
+        let is_plate_first = match (reading_config.speaker.name_region, paragraph.symbols.first()) {
+            (Some(name_region), Some(symbol)) => speaker::in_name_region(&symbol.bounds, name_region, reading_config.capture_size),
+            _ => false,
+        };
+        if is_plate_first {
+            // When the first line can't be found in the text, the text is kept whole
+            if let Some(dialogue_text) = normalized_text.trim().strip_prefix(first_line.trim()) {
+                let dialogue_text = dialogue_text.trim().to_string();
+                plate_speaker = Some(first_line.trim().to_string());
+                if dialogue_text.is_empty() {
+                    continue;
                 }
+                normalized_text = dialogue_text;
+            }
+        }
+        let (speaker_name, normalized_text) = speaker::split_speaker(&normalized_text, &first_line, &reading_config.cast, reading_config.speaker.patterns);
+        // Taken whether or not this block is kept, so a skipped block uses it up
+        let plate_name = plate_speaker.take();
+        let speaker_name = speaker_name.or(plate_name);
+        let block_text = match reading_config.rule_set.apply(&normalized_text) {
+            RuleOutcome::Kept(block_text) => block_text,
+            RuleOutcome::Ignored(_) => continue,
+        };
+        // Normalized or rewritten text no longer lines up with the OCR characters
+        if block_text != paragraph.text() {
+            confidences.clear();
+        }
+        if kakasi::is_japanese(&block_text) == IsJapanese::False {
+            continue;
+        }
+        // WORDS
+        let words = match &reading_config.tokenizer {
+            Some(tokenizer) => tokenizer.tokenize(&block_text),
+            None => Vec::new(),
+        };
+        // Printed furigana follow the OCR text, so they are only used when nothing was split off or rewritten
+        let kana_text: String = if reading_config.furigana.use_as_reading && !paragraph.furigana.is_empty() && block_text.trim() == paragraph.text().trim() {
+            kakasi::convert(&furigana::reading(&paragraph)).hiragana
+        } else if !words.is_empty() {
+            tokenizer::line_reading(&words)
+        } else {
+            kakasi::convert(&block_text).hiragana
+        };
+        // UNCHANGED SINCE LAST CAPTURE
+        let key = packet_key(speaker_name.as_deref(), &block_text);
+        if let Some(previous_packet) = reading_config.previous_packets.iter().find(|previous_packet| previous_packet.key() == key) {
+            packets.push(TranslationPacket {
+                bounds: paragraph.bounds.clone(),
+                confidences,
+                ..previous_packet.clone()
+            });
+            continue;
+        }
+        // DEEPL TRANSLATION
+        let speaker = match speaker_name {
+            Some(speaker_name) => match glossary.get(&speaker_name) {
+                Some(translation) => Some(SpeakerName { jp: speaker_name, eng: translation.clone() }),
+                // Names are translated once and kept in the cast list
+                None => match send_deepl_api_request(&speaker_name, deepl_token).await {
+                    Ok(response) => {
+                        let translation = parse_deepl_response(response);
+                        glossary.insert(speaker_name.clone(), translation.clone());
+                        glossary_queue_clone.lock().unwrap().push(CastMember { name: speaker_name.clone(), translation: translation.clone() });
+                        Some(SpeakerName { jp: speaker_name, eng: translation })
+                    },
+                    Err(_) => Some(SpeakerName { jp: speaker_name, eng: String::new() }),
+                },
+            },
+            None => None,
+        };
+        match send_deepl_api_request(&block_text, deepl_token).await {
+            Ok(response) => {
+                godot_print!("DeepL response received!");
+                let translation_text = parse_deepl_response(response);
+                // WRAPUP
+                packets.push(TranslationPacket {
+                    jp_text: block_text,
+                    jp_read: kana_text,
+                    eng_text: translation_text,
+                    bounds: paragraph.bounds.clone(),
+                    confidences,
+                    speaker,
+                    words,
+                });
+                let mut box_queue = box_queue_clone.lock().unwrap();
+                box_queue.push(());
+            }
+            Err(error) => {
+                godot_print!("DeepL error!");
+                let eow = ErrorOrWarning {
+                    string: (format!("Failed to communicate with DeepL, check credentials!\n{}", error)),
+                    is_warning: false,
+                };
+                return Err(eow);
             }
         }
     }
     Ok(packets)
 }
 
+// OCR paragraphs in reading order, regrouped into dialogue units
+fn read_units(response_json: &Value, segment_config: &SegmentConfig, furigana_config: &FuriganaConfig, upscale: u32) -> Vec<ocr::OcrParagraph> {
+    let mut paragraphs = ocr::read_paragraphs(response_json, upscale);
+    if furigana_config.strip {
+        furigana::extract_furigana(&mut paragraphs);
+    }
+    ocr::sort_reading_order(&mut paragraphs);
+    segment::segment(paragraphs, segment_config)
+}
+
+// Underlines characters that OCR was unsure about
+fn mark_low_confidence(jp_text: &str, confidences: &[f32], mark_confidence: f32) -> String {
+    let mut marked_text = String::new();
+    for (i, c) in jp_text.chars().enumerate() {
+        let escaped = if c == '[' { "[lb]".to_string() } else { c.to_string() };
+        if confidences.get(i).is_some_and(|confidence| *confidence < mark_confidence) {
+            marked_text.push_str(&format!("[color=#d9a05b][u]{}[/u][/color]", escaped));
+        } else {
+            marked_text.push_str(&escaped);
+        }
+    }
+    marked_text
+}
+
+fn read_user_settings() -> Result<UserSettings, String> {
+    let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::READ).ok_or("user://user_settings.toml could not be opened.")?;
+    let contents = file.get_as_text().to_string();
+    file.close();
+    toml::from_str::<UserSettings>(&contents).map_err(|e| e.to_string())
+}
+
+// Preprocess a crop for OCR, and keep the crop itself for the history
+fn prepare_capture(crop: RgbaImage, preprocess_config: &PreprocessConfig, is_preview: bool, history_id: Option<u64>) -> ScreenCapture {
+    let fingerprint = capture::fingerprint(&crop);
+    let crop_png_bytes = if is_preview { Vec::new() } else { capture::encode_png(crop.clone()).into_inner() };
+    let png_buffer = capture::encode_png(preprocess::preprocess(crop, preprocess_config));
+    ScreenCapture { png_buffer, crop_png_bytes, is_preview, fingerprint, history_id }
+}
+
+// ________________________________________
+// Data files the user saved in res://dict
+// are read on the main thread, which is the
+// only one that sees res://, and parsed in
+// the background. Missing files are named
+// by warn_missing_data instead
+// ----------------------------------------
+fn load_in_background<T: Send + 'static>(path: &str, parse: fn(&[u8]) -> Result<T, String>, slot: Arc<Mutex<Option<T>>>) {
+    if !FileAccess::file_exists(path) {
+        return;
+    }
+    let bytes = FileAccess::get_file_as_bytes(path).to_vec();
+    thread::spawn(move || {
+        match parse(&bytes) {
+            Ok(data) => *slot.lock().unwrap() = Some(data),
+            Err(error) => godot_print!("{}", error),
+        }
+    });
+}
+
+// Each word links to where it starts in the line, so hovering or clicking
+// it looks it up. Without words every character links on its own
+fn link_words(jp_text: &str, confidences: &[f32], mark_confidence: f32, words: &[Word], highlights: &HashMap<usize, &str>, highlight_style: i32) -> String {
+    let chars: Vec<char> = jp_text.chars().collect();
+    let mut linked_text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let length = words.iter().find(|word| word.start == i).map_or(1, |word| word.surface.chars().count().max(1));
+        let end = (i + length).min(chars.len());
+        let text: String = chars[i..end].iter().collect();
+        let mut marked_text = mark_low_confidence(&text, confidences.get(i..end).unwrap_or(&[]), mark_confidence);
+        if let Some(color) = highlights.get(&i) {
+            marked_text = difficulty::highlight(&marked_text, color, highlight_style);
+        }
+        linked_text.push_str(&format!("[url={}]{}[/url]", i, marked_text));
+        i = end;
+    }
+    linked_text
+}
+
+fn word_start(words: &[Word], offset: usize) -> usize {
+    words.iter()
+        .find(|word| word.start <= offset && offset < word.start + word.surface.chars().count())
+        .map_or(offset, |word| word.start)
+}
+
 fn parse_deepl_response(response_json: Value) -> String {
     if let Some(translation) = response_json["translations"][0]["text"].as_str() {
         return translation.to_string();
@@ -347,11 +770,7 @@ fn parse_deepl_response(response_json: Value) -> String {
 impl System {
     #[func]
     fn list_windows(&self) {
-        let windows = Window::all().unwrap();
-        let vec_string: Vec<String> = windows.into_iter()
-            .filter(|w| !w.is_minimized())
-            .map(|w| format!("{}", w.title()))
-            .collect();
+        let vec_string = capture::list_window_titles();
         let mut window_selector = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer10/OptionButton");
         window_selector.clear();
         window_selector.add_item("None");
@@ -360,6 +779,13 @@ impl System {
             godot_print!("WINDOW FOUND: {:?}", string);
             window_selector.add_item(string);
         }
+        self.change_target_window();
+    }
+
+    // A new source gets a new ScreenCast session, the old one is closed
+    #[func]
+    fn change_target_window(&self) {
+        self.portal.lock().unwrap().close();
     }
 
     fn add_box_to_conveyor(&self) {
@@ -391,9 +817,18 @@ impl System {
         reset(box_conveyor.clone().upcast());
     }
 
-    fn read_screen(&mut self, png_buffer: Cursor<Vec<u8>>) {
+    fn read_screen(&mut self, png_buffer: Cursor<Vec<u8>>, is_reread: bool) {
         self.system_state = SystemState::READING;
-        let base64_encoded_image = encode(&png_buffer.into_inner());
+        // Bounds are scaled back to the crop, so its size is the preprocessed size without the upscale
+        let upscale = self.get_preprocess_config().upscale.max(1);
+        let (width, height) = image::io::Reader::new(Cursor::new(png_buffer.get_ref()))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or((1, 1));
+        let capture_size = (width / upscale, height / upscale);
+        self.last_upscale = upscale;
+        let base64_encoded_image = STANDARD.encode(png_buffer.into_inner());
         let request_body = create_vision_api_request(base64_encoded_image);
         // CREDENTIALS
         let gcloud_token = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit").get_text().to_string();
@@ -403,13 +838,38 @@ impl System {
         let packets_queue_clone = Arc::clone(&self.packets_queue);
         let error_queue_clone = Arc::clone(&self.error_queue);
         let box_queue_clone = Arc::clone(&self.box_queue);
+        let last_response_clone = Arc::clone(&self.last_response);
+        let glossary_queue_clone = Arc::clone(&self.glossary_queue);
+        let min_confidence_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer27/LineEdit").get_text();
+        let rule_set = match self.get_rule_set() {
+            Ok(rule_set) => rule_set,
+            Err(error) => {
+                self.log_error(format!("Text rules could not be used! {}", error));
+                self.system_state = SystemState::IDLE;
+                return;
+            }
+        };
+        let reading_config = ReadingConfig {
+            segment: self.get_segment_config(),
+            furigana: self.get_furigana_config(),
+            normalize: self.get_normalize_config(),
+            min_confidence: min_confidence_text.to_string().parse::<f32>().unwrap_or(DEFAULT_MIN_CONFIDENCE),
+            rule_set,
+            speaker: self.get_speaker_config(),
+            cast: self.get_cast(),
+            capture_size,
+            upscale,
+            previous_packets: if is_reread { Vec::new() } else { self.previous_packets.clone() },
+            tokenizer: self.tokenizer.clone(),
+        };
 
         thread::spawn(move || {
             tokio::runtime::Runtime::new().unwrap().block_on(async {
                 match send_vision_api_request(request_body, &gcloud_token, &project_id).await {
                     Ok(response) => {
                         godot_print!("Google Cloud Vision response received!");
-                        let result = parse_vision_response(response.clone(), &deepl_token, box_queue_clone).await;
+                        *last_response_clone.lock().unwrap() = Some(response.clone());
+                        let result = parse_vision_response(response.clone(), &deepl_token, &reading_config, glossary_queue_clone, box_queue_clone).await;
                         match result {
                             Ok(packets) => {
                                 if packets.is_empty() {
@@ -447,19 +907,13 @@ impl System {
     #[func]
     fn capture_screen(&mut self, is_preview: bool) {
         self.reset_box_conveyor();
-        let up_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit").get_text();
-        let down_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit2").get_text();
-        let left_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit3").get_text();
-        let right_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit4").get_text();
-        let margin_up = up_text.to_string().parse::<u32>().unwrap();
-        let margin_down = down_text.to_string().parse::<u32>().unwrap();
-        let margin_left = left_text.to_string().parse::<u32>().unwrap();
-        let margin_right = right_text.to_string().parse::<u32>().unwrap();
+        let reading_area = self.get_reading_area();
+        let preprocess_config = self.get_preprocess_config();
 
         self.system_state = SystemState::CAPTURING;
-        let mut png_buffer = Cursor::new(Vec::new());
         let screen_queue_clone = Arc::clone(&self.screen_queue);
         let error_queue_clone = Arc::clone(&self.error_queue);
+        let portal_clone = Arc::clone(&self.portal);
         let window_selector = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer10/OptionButton");
         let window_title = window_selector.get_text().to_string();
         if window_title == "None" {
@@ -471,215 +925,727 @@ impl System {
             return;
         }
         thread::spawn(move || {
-            let windows = Window::all().unwrap();
-            let window = windows.into_iter().find(|w| w.title() == window_title && !w.is_minimized());
-
-            if let Some(window) = window {
-                let image = match window.capture_image() {
-                    Ok(img) => img,
-                    Err(e) => {
-                        let mut error_queue = error_queue_clone.lock().unwrap();
-                        error_queue.push(ErrorOrWarning {
-                            string: format!("Window capturing failure: {}", e),
-                            is_warning: false,
-                        });
-                        return;
+            match capture::capture_window(&window_title, &portal_clone, reading_area.up, reading_area.down, reading_area.left, reading_area.right) {
+                Ok(cropped_image) => {
+                    let screen_capture = prepare_capture(cropped_image, &preprocess_config, is_preview, None);
+                    if !screen_capture.png_buffer.get_ref().is_empty() {
+                        let mut screen_queue = screen_queue_clone.lock().unwrap();
+                        screen_queue.push(screen_capture);
                     }
-                };
+                },
+                Err(error) => {
+                    let mut error_queue = error_queue_clone.lock().unwrap();
+                    error_queue.push(ErrorOrWarning {
+                        string: error,
+                        is_warning: false,
+                    });
+                }
+            }
+        });
+    }
+
+    #[func]
+    fn open_image_file(&self) {
+        let mut file_dialog = self.base().get_node_as::<FileDialog>("FileDialog");
+        file_dialog.popup_centered();
+    }
+
+    #[func]
+    fn read_image_file(&mut self, path: GString) {
+        let path = path.to_string();
+        self.read_external_image(move || capture::load_image_file(&path));
+    }
+
+    #[func]
+    fn read_dropped_files(&mut self, files: PackedStringArray) {
+        // Only the first dropped file is read: one screenshot per reading
+        if let Some(path) = files.as_slice().first() {
+            let path = path.to_string();
+            self.read_external_image(move || capture::load_image_file(&path));
+        }
+    }
+
+    #[func]
+    fn paste_clipboard_image(&mut self) {
+        let display_server = DisplayServer::singleton();
+        let clipboard_image = if display_server.clipboard_has_image() { display_server.clipboard_get_image() } else { None };
+        if let Some(clipboard_image) = clipboard_image {
+            let png_bytes = clipboard_image.save_png_to_buffer().to_vec();
+            self.read_external_image(move || capture::decode_image(&png_bytes));
+        } else {
+            self.clear_errors();
+            self.log_warning("Clipboard does not contain an image!".to_string());
+        }
+    }
 
-                let new_width = image.width().saturating_sub(margin_left + margin_right);
-                let new_height = image.height().saturating_sub(margin_up + margin_down);
-                let cropped_image = image.view(margin_left, margin_up, new_width, new_height).to_image();
-                let dynamic_img = DynamicImage::ImageRgba8(cropped_image);
-                dynamic_img.write_to(&mut png_buffer, ImageOutputFormat::Png).unwrap();
+    // Screenshots from disk or clipboard skip the reading area crop,
+    // but go through the same preprocessing and reading as a window capture
+    fn read_external_image<F>(&mut self, load_image: F) where F: FnOnce() -> Result<RgbaImage, String> + Send + 'static {
+        if !matches!(self.system_state, SystemState::IDLE) {
+            godot_print!("Still reading, the image was not read!");
+            return;
+        }
+        self.reset_box_conveyor();
+        let preprocess_config = self.get_preprocess_config();
 
-                if !png_buffer.get_ref().is_empty() {
+        self.system_state = SystemState::CAPTURING;
+        let screen_queue_clone = Arc::clone(&self.screen_queue);
+        let error_queue_clone = Arc::clone(&self.error_queue);
+        thread::spawn(move || {
+            match load_image() {
+                Ok(image) => {
                     let mut screen_queue = screen_queue_clone.lock().unwrap();
-                    let screen_capture = ScreenCapture { png_buffer, is_preview };
-                    screen_queue.push(screen_capture);
+                    screen_queue.push(prepare_capture(image, &preprocess_config, false, None));
+                },
+                Err(error) => {
+                    let mut error_queue = error_queue_clone.lock().unwrap();
+                    error_queue.push(ErrorOrWarning {
+                        string: error,
+                        is_warning: false,
+                    });
                 }
-            } else {
-                let mut error_queue = error_queue_clone.lock().unwrap();
-                error_queue.push(ErrorOrWarning {
-                    string: format!("Target window not found: make sure to refresh window list!"),
-                    is_warning: false,
-                });
-                return;
             }
         });
     }
 
-    #[func]
-    fn save_credentials(&self) {
-        // CREDENTIALS
-        let gcloud_token = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit").get_text().to_string();
-        let project_id = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer8/LineEdit").get_text().to_string();
-        let deepl_token = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer9/LineEdit").get_text().to_string();
+    fn is_typing(&self) -> bool {
+        match self.base().get_viewport().and_then(|viewport| viewport.gui_get_focus_owner()) {
+            Some(focus_owner) => focus_owner.is_class("LineEdit") || focus_owner.is_class("TextEdit"),
+            None => false,
+        }
+    }
 
-        let user_credentials = Some(UserCredentials {
-            gcloud_token,
-            project_id,
-            deepl_token,
-        });
+    fn set_screen_preview(&self, png_bytes: &[u8]) {
+        let mut screen_image = Image::new_gd();
+        screen_image.load_png_from_buffer(&PackedByteArray::from(png_bytes));
+        let screen_texture = ImageTexture::create_from_image(&screen_image).expect("Failed to create ImageTexture!");
+        let mut screen_textrect = self.base().get_node_as::<TextureRect>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Reader/PanelContainer/VBoxContainer/screen_textrect");
+        screen_textrect.set_texture(&screen_texture);
+    }
+
+    fn refresh_history(&self) {
+        let mut vbox = self.base().get_node_as::<VBoxContainer>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/History/PanelContainer/ScrollContainer/VBoxContainer");
+        reset(vbox.clone().upcast());
+        for entry in self.history.entries() {
+            let history_entry = load::<PackedScene>("res://history_entry.tscn").instantiate_as::<PanelContainer>();
+            let mut thumbnail = history_entry.get_node_as::<TextureRect>("HBoxContainer/thumbnail");
+            let mut timestamp = history_entry.get_node_as::<RichTextLabel>("HBoxContainer/VBoxContainer/timestamp");
+            let mut read_button = history_entry.get_node_as::<Button>("HBoxContainer/VBoxContainer/read_button");
+            let mut save_button = history_entry.get_node_as::<Button>("HBoxContainer/VBoxContainer/save_button");
+            let mut thumbnail_image = Image::new_gd();
+            thumbnail_image.load_png_from_buffer(&PackedByteArray::from(entry.png_bytes.as_slice()));
+            if let Some(thumbnail_texture) = ImageTexture::create_from_image(&thumbnail_image) {
+                thumbnail.set_texture(&thumbnail_texture);
+            }
+            timestamp.set_text(&entry.timestamp);
+            let id = entry.id as i64;
+            read_button.connect("pressed", &Callable::from_object_method(&self.to_gd(), "reread_capture").bindv(&varray![id]));
+            save_button.connect("pressed", &Callable::from_object_method(&self.to_gd(), "open_save_dialog").bindv(&varray![id]));
+            make_child(&mut vbox, history_entry.upcast());
+        }
+    }
 
-        let mut user_settings = UserSettings {
-            user_credentials,
-            reading_area: None,
-            packet_config: None,
+    // The crop is preprocessed again, so a re-read follows the current settings
+    #[func]
+    fn reread_capture(&mut self, id: i64) {
+        if !matches!(self.system_state, SystemState::IDLE) {
+            godot_print!("Still reading, the capture was not read again!");
+            return;
+        }
+        let png_bytes = match self.history.get(id as u64) {
+            Some(entry) => entry.png_bytes.clone(),
+            None => return,
         };
+        self.clear_errors();
+        self.reset_box_conveyor();
+        let mut tab_container = self.base().get_node_as::<TabContainer>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer");
+        tab_container.set_current_tab(0);
+        let preprocess_config = self.get_preprocess_config();
 
-        // PULL
-        if FileAccess::file_exists("user://user_settings.toml") {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::READ).expect("Failed to open file!");
-            let contents = file.get_as_text().to_string();
-            file.close();
-            match toml::from_str::<UserSettings>(&contents) {
-                Ok(pulled_user_settings) => {
-                    if let Some(reading_area) = pulled_user_settings.reading_area {
-                        user_settings.reading_area =  Some(reading_area);
-                    }
-                    if let Some(packet_config) = pulled_user_settings.packet_config {
-                        user_settings.packet_config =  Some(packet_config);
-                    }
+        self.system_state = SystemState::CAPTURING;
+        let screen_queue_clone = Arc::clone(&self.screen_queue);
+        let error_queue_clone = Arc::clone(&self.error_queue);
+        thread::spawn(move || {
+            match capture::decode_image(&png_bytes) {
+                Ok(image) => {
+                    let mut screen_queue = screen_queue_clone.lock().unwrap();
+                    screen_queue.push(prepare_capture(image, &preprocess_config, false, Some(id as u64)));
                 },
-                _ => {},
+                Err(error) => {
+                    let mut error_queue = error_queue_clone.lock().unwrap();
+                    error_queue.push(ErrorOrWarning {
+                        string: error,
+                        is_warning: false,
+                    });
+                }
             }
-        }
+        });
+    }
 
-        // PUSH
-        if let Ok(serialized) = toml::to_string(&user_settings) {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::WRITE).expect("Internal Error: Failed to open file!");
-            file.store_string(&serialized);
-            file.close();
-            let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
-            self.clear_errors();
-            self.reset_box_conveyor();
-            console.set_text("Credentials saved!");
-        } else { self.log_error("Failed to save Credentials! You may have used invalid values.".to_string()); }
+    #[func]
+    fn open_save_dialog(&mut self, id: i64) {
+        self.history_save_id = Some(id as u64);
+        let mut save_dialog = self.base().get_node_as::<FileDialog>("SaveDialog");
+        save_dialog.set_current_file(&format!("vets_capture_{}.png", id));
+        save_dialog.popup_centered();
     }
 
     #[func]
-    fn save_reading_area(&self) {
-        // READING AREA
+    fn save_capture(&mut self, path: GString) {
+        let entry = match self.history_save_id.take().and_then(|id| self.history.get(id)) {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.clear_errors();
+        match std::fs::write(path.to_string(), &entry.png_bytes) {
+            Ok(()) => {
+                let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
+                console.set_text(&format!("Capture saved to {}!", path));
+            },
+            Err(error) => self.log_error(format!("Failed to save capture: {}", error)),
+        }
+    }
+
+    fn get_reading_area(&self) -> ReadingArea {
         let up_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit").get_text();
         let down_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit2").get_text();
         let left_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit3").get_text();
         let right_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit4").get_text();
-        let up = up_text.to_string().parse::<u32>().unwrap();
-        let down = down_text.to_string().parse::<u32>().unwrap();
-        let left = left_text.to_string().parse::<u32>().unwrap();
-        let right = right_text.to_string().parse::<u32>().unwrap();
-
-        let reading_area = Some(ReadingArea {
-            up,
-            down,
-            left,
-            right,
-        });
+        ReadingArea {
+            up: up_text.to_string().parse::<u32>().unwrap(),
+            down: down_text.to_string().parse::<u32>().unwrap(),
+            left: left_text.to_string().parse::<u32>().unwrap(),
+            right: right_text.to_string().parse::<u32>().unwrap(),
+        }
+    }
+
+    fn get_preprocess_config(&self) -> PreprocessConfig {
+        let upscale_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer13/LineEdit").get_text();
+        let upscale_filter = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer14/OptionButton").get_selected_id();
+        let grayscale = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer15/CheckButton").is_pressed();
+        let contrast_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer16/LineEdit").get_text();
+        let threshold_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer17/LineEdit").get_text();
+        let invert = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer18/CheckButton").is_pressed();
+        let denoise = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer19/CheckButton").is_pressed();
+        let color_key_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer20/LineEdit").get_text();
+        let tolerance_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer20/LineEdit2").get_text();
+        PreprocessConfig {
+            upscale: upscale_text.to_string().parse::<u32>().unwrap_or(1).max(1).min(4),
+            upscale_filter,
+            grayscale,
+            contrast: contrast_text.to_string().parse::<f32>().unwrap_or(0.0),
+            threshold: threshold_text.to_string().parse::<u8>().unwrap_or(0),
+            invert,
+            denoise,
+            color_key: preprocess::parse_color_key(&color_key_text.to_string()),
+            color_key_tolerance: tolerance_text.to_string().parse::<u8>().unwrap_or(32),
+        }
+    }
 
+    fn get_segment_config(&self) -> SegmentConfig {
+        let merge = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer21/CheckButton").is_pressed();
+        let merge_distance_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer22/LineEdit").get_text();
+        let split = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer23/CheckButton").is_pressed();
+        let split_gap_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer24/LineEdit").get_text();
+        SegmentConfig {
+            merge,
+            merge_distance: merge_distance_text.to_string().parse::<f32>().unwrap_or(1.0).max(0.0),
+            split,
+            split_gap: split_gap_text.to_string().parse::<f32>().unwrap_or(1.5).max(0.0),
+        }
+    }
 
-        let mut user_settings = UserSettings {
-            user_credentials: None,
-            reading_area,
-            packet_config: None,
-        };
+    fn get_normalize_config(&self) -> NormalizeConfig {
+        let nfkc = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer30/CheckButton").is_pressed();
+        let confusables = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer31/CheckButton").is_pressed();
+        let ellipsis = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer32/CheckButton").is_pressed();
+        let dashes = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer33/CheckButton").is_pressed();
+        let stray_marks = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer34/CheckButton").is_pressed();
+        NormalizeConfig {
+            nfkc,
+            confusables,
+            ellipsis,
+            dashes,
+            stray_marks,
+        }
+    }
 
-        // PULL
-        if FileAccess::file_exists("user://user_settings.toml") {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::READ).expect("Failed to open file!");
-            let contents = file.get_as_text().to_string();
-            file.close();
-            match toml::from_str::<UserSettings>(&contents) {
-                Ok(pulled_user_settings) => {
-                    if let Some(user_credentials) = pulled_user_settings.user_credentials {
-                        user_settings.user_credentials =  Some(user_credentials);
-                    }
-                    if let Some(packet_config) = pulled_user_settings.packet_config {
-                        user_settings.packet_config =  Some(packet_config);
-                    }
-                },
-                _ => {},
+    // Lists what each normalization rule changed in the last OCR reading
+    #[func]
+    fn refresh_normalization_preview(&self) {
+        let mut normalize_preview = self.base().get_node_as::<RichTextLabel>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/PanelContainer4/VBoxContainer/normalize_preview");
+        let last_response = self.last_response.lock().unwrap().clone();
+        let response_json = match last_response {
+            Some(response_json) => response_json,
+            None => {
+                normalize_preview.set_text("[color=#777777]Read the screen once to preview the changes.");
+                return;
+            }
+        };
+        let normalize_config = self.get_normalize_config();
+        let mut preview_text = String::new();
+        for unit in read_units(&response_json, &self.get_segment_config(), &self.get_furigana_config(), self.last_upscale) {
+            let (_, changes) = normalize::normalize(&unit.text(), &normalize_config);
+            for change in changes {
+                preview_text.push_str(&format!("[color=#777777]{}:[/color] {} → {}\n", change.rule, change.before.trim(), change.after.trim()));
             }
         }
+        if preview_text.is_empty() {
+            preview_text.push_str("[color=#777777]Nothing to change in the last reading.");
+        }
+        normalize_preview.set_text(&preview_text);
+    }
 
-        // PUSH
-        if let Ok(serialized) = toml::to_string(&user_settings) {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::WRITE).expect("Internal Error: Failed to open file!");
-            file.store_string(&serialized);
-            file.close();
-            let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
-            self.clear_errors();
-            self.reset_box_conveyor();
-            console.set_text("Crop Values saved!");
-        } else { self.log_error("Failed to save Crop Values! You may have used invalid values.".to_string()); }
+    fn get_rule_set(&self) -> Result<RuleSet, String> {
+        let rules_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit2").get_text().to_string();
+        RuleSet::new(rules::parse_rules(&rules_text)?)
     }
 
-    #[func]
-    fn save_packet_config(&self) {
-        // TRANSLATION PACKET CONFIG
-        let jp_font = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer4/OptionButton").get_selected_id();
-        let font_size_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer/LineEdit").get_text();
-        let font_size = font_size_text.to_string().parse::<usize>().unwrap();
-        let romaji = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer2/CheckButton").is_pressed();
+    fn get_game_profile(&self) -> String {
+        let profile_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer58/LineEdit").get_text().to_string();
+        match profile_text.trim() {
+            "" => "Default".to_string(),
+            profile => profile.to_string(),
+        }
+    }
 
-        let packet_config = Some(PacketConfig {
-            jp_font,
-            font_size,
-            romaji,
-        });
+    // Rules and cast belong to the game profile, so switching profiles swaps them.
+    // Window titles change with every scene in many games, so they are not used as keys
+    #[func]
+    fn load_game_settings(&self) {
+        let mut rules_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit2");
+        let mut cast_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit3");
+        let user_settings = self.pull_user_settings();
+        let game_profile = self.get_game_profile();
+        let game_rules = user_settings.text_rules.and_then(|mut text_rules| text_rules.remove(&game_profile));
+        let game_cast = user_settings.casts.and_then(|mut casts| casts.remove(&game_profile));
+        rules_text.set_text(&rules::format_rules(&game_rules.unwrap_or_default()));
+        cast_text.set_text(&speaker::format_cast(&game_cast.unwrap_or_default()));
+    }
 
+    fn get_speaker_config(&self) -> SpeakerConfig {
+        let patterns = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer35/CheckButton").is_pressed();
+        let name_region_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer36/LineEdit").get_text();
+        SpeakerConfig {
+            patterns,
+            name_region: speaker::parse_name_region(&name_region_text.to_string()),
+        }
+    }
 
-        let mut user_settings = UserSettings {
-            user_credentials: None,
-            reading_area: None,
-            packet_config,
-        };
+    fn get_cast(&self) -> Vec<CastMember> {
+        let cast_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit3").get_text().to_string();
+        speaker::parse_cast(&cast_text)
+    }
 
-        // PULL
-        if FileAccess::file_exists("user://user_settings.toml") {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::READ).expect("Failed to open file!");
-            let contents = file.get_as_text().to_string();
-            file.close();
-            match toml::from_str::<UserSettings>(&contents) {
-                Ok(pulled_user_settings) => {
-                    if let Some(user_credentials) = pulled_user_settings.user_credentials {
-                        user_settings.user_credentials =  Some(user_credentials);
-                    }
-                    if let Some(reading_area) = pulled_user_settings.reading_area {
-                        user_settings.reading_area =  Some(reading_area);
-                    }
-                },
-                _ => {},
+    // Names translated during a reading join the cast list of the game
+    fn add_to_cast(&self, new_names: Vec<CastMember>) {
+        let mut cast = self.get_cast();
+        for new_name in new_names {
+            match cast.iter_mut().find(|member| member.name == new_name.name) {
+                Some(member) => member.translation = new_name.translation,
+                None => cast.push(new_name),
             }
         }
+        let mut cast_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit3");
+        cast_text.set_text(&speaker::format_cast(&cast));
+        let mut user_settings = self.pull_user_settings();
+        user_settings.casts.get_or_insert_with(HashMap::new).insert(self.get_game_profile(), cast);
+        self.push_user_settings(&user_settings, "Cast");
+    }
 
-        // PUSH
-        if let Ok(serialized) = toml::to_string(&user_settings) {
-            let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::WRITE).expect("Internal Error: Failed to open file!");
-            file.store_string(&serialized);
-            file.close();
-            let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
-            self.clear_errors();
-            self.reset_box_conveyor();
-            console.set_text("Translation Packet Config saved!");
-        } else { self.log_error("Failed to save Packet Config Settings! You may have used invalid values.".to_string()); }
+    #[func]
+    fn test_text_rules(&self) {
+        let test_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer29/LineEdit").get_text().to_string();
+        let mut rules_result = self.base().get_node_as::<RichTextLabel>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/PanelContainer3/VBoxContainer/rules_result");
+        match self.get_rule_set() {
+            Ok(rule_set) => match rule_set.apply(&test_text) {
+                RuleOutcome::Kept(block_text) => rules_result.set_text(&format!("Translated as: {}", block_text)),
+                RuleOutcome::Ignored(i) => rules_result.set_text(&format!("Ignored by rule {}", i + 1)),
+            },
+            Err(error) => rules_result.set_text(&format!("[color=#d9705b]{}", error)),
+        }
     }
 
-    fn load_user_settings(&self) {
-        let mut file = FileAccess::open("user://user_settings.toml", ModeFlags::READ).expect("Failed to open file!");
-        let contents = file.get_as_text().to_string();
-        file.close();
-        match toml::from_str::<UserSettings>(&contents) {
-            Ok(user_settings) => {
-                // SET CREDENTIALS
-                if let Some(user_credentials) = user_settings.user_credentials {
-                    let mut gcloud_token = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit");
-                    let mut project_id = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer8/LineEdit");
-                    let mut deepl_token = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer9/LineEdit");
-                    gcloud_token.set_text(&user_credentials.gcloud_token);
-                    project_id.set_text(&user_credentials.project_id);
-                    deepl_token.set_text(&user_credentials.deepl_token);
-                }
+    fn get_furigana_config(&self) -> FuriganaConfig {
+        let strip = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer25/CheckButton").is_pressed();
+        let use_as_reading = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer26/CheckButton").is_pressed();
+        FuriganaConfig {
+            strip,
+            use_as_reading,
+        }
+    }
+
+    // Re-segments the last OCR reading with the current settings,
+    // so the units can be tuned without spending another request
+    #[func]
+    fn refresh_segmentation_preview(&self) {
+        let mut segment_preview = self.base().get_node_as::<RichTextLabel>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/PanelContainer2/VBoxContainer/segment_preview");
+        let last_response = self.last_response.lock().unwrap().clone();
+        let response_json = match last_response {
+            Some(response_json) => response_json,
+            None => {
+                segment_preview.set_text("[color=#777777]Read the screen once to preview its units.");
+                return;
+            }
+        };
+        let units = read_units(&response_json, &self.get_segment_config(), &self.get_furigana_config(), self.last_upscale);
+        let mut preview_text = String::new();
+        for (i, unit) in units.iter().enumerate() {
+            preview_text.push_str(&format!("[color=#777777]{}.[/color] {}\n", i + 1, unit.text().trim()));
+        }
+        segment_preview.set_text(&preview_text);
+    }
+
+    fn auto_capture_tick(&mut self, delta: f32) {
+        let mut auto_capture_toggle = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer11/CheckButton");
+        if !auto_capture_toggle.is_pressed() {
+            self.auto_capture.reset();
+            return;
+        }
+        // ---- CATCH ERRORS ----
+        let mut error_queue = self.error_queue.lock().unwrap();
+        if let Some(eow) = error_queue.pop() {
+            drop(error_queue);
+            auto_capture_toggle.set_pressed(false);
+            self.log_error(format!("Auto Capture stopped!\n{}", eow.string));
+            return;
+        }
+        drop(error_queue);
+        // ---- CATCH SAMPLES ----
+        let mut sample_queue = self.sample_queue.lock().unwrap();
+        if let Some(sample) = sample_queue.pop() {
+            drop(sample_queue);
+            self.save_portal_token();
+            self.auto_capture.is_sampling = false;
+            if self.auto_capture.observe(&sample.fingerprint) {
+                godot_print!("AUTO CAPTURE: new text detected!");
+                self.reset_box_conveyor();
+                self.system_state = SystemState::CAPTURING;
+                let preprocess_config = self.get_preprocess_config();
+                let screen_queue_clone = Arc::clone(&self.screen_queue);
+                thread::spawn(move || {
+                    let screen_capture = prepare_capture(sample.crop, &preprocess_config, false, None);
+                    screen_queue_clone.lock().unwrap().push(screen_capture);
+                });
+            }
+            return;
+        }
+        drop(sample_queue);
+        // ---- SAMPLE READING AREA ----
+        let interval_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer12/LineEdit").get_text();
+        let interval = interval_text.to_string().parse::<f32>().unwrap_or(1.0).max(0.2);
+        self.auto_capture.time_accumulator += delta;
+        if self.auto_capture.is_sampling || self.auto_capture.time_accumulator < interval {
+            return;
+        }
+        self.auto_capture.time_accumulator = 0.0;
+        let window_title = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer10/OptionButton").get_text().to_string();
+        if window_title == "None" {
+            auto_capture_toggle.set_pressed(false);
+            self.log_warning("Please select a target window!".to_string());
+            return;
+        }
+        let reading_area = self.get_reading_area();
+        let sample_queue_clone = Arc::clone(&self.sample_queue);
+        let error_queue_clone = Arc::clone(&self.error_queue);
+        let portal_clone = Arc::clone(&self.portal);
+        self.auto_capture.is_sampling = true;
+        thread::spawn(move || {
+            match capture::capture_window(&window_title, &portal_clone, reading_area.up, reading_area.down, reading_area.left, reading_area.right) {
+                Ok(cropped_image) => {
+                    // Only the fingerprint is compared, the crop is preprocessed once it is read
+                    let fingerprint = capture::fingerprint(&cropped_image);
+                    let mut sample_queue = sample_queue_clone.lock().unwrap();
+                    sample_queue.push(Sample { crop: cropped_image, fingerprint });
+                },
+                Err(error) => {
+                    let mut error_queue = error_queue_clone.lock().unwrap();
+                    error_queue.push(ErrorOrWarning {
+                        string: error,
+                        is_warning: false,
+                    });
+                }
+            }
+        });
+    }
+
+    #[func]
+    fn save_credentials(&self) {
+        // CREDENTIALS
+        let gcloud_token = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit").get_text().to_string();
+        let project_id = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer8/LineEdit").get_text().to_string();
+        let deepl_token = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer9/LineEdit").get_text().to_string();
+
+        let user_credentials = Some(UserCredentials {
+            gcloud_token,
+            project_id,
+            deepl_token,
+        });
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.user_credentials = user_credentials;
+
+        if self.push_user_settings(&user_settings, "Credentials") {
+            self.reset_box_conveyor();
+            self.log_message("Credentials saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_reading_area(&self) {
+        // READING AREA
+        let reading_area = Some(self.get_reading_area());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.reading_area = reading_area;
+
+        if self.push_user_settings(&user_settings, "Crop Values") {
+            self.reset_box_conveyor();
+            self.log_message("Crop Values saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_packet_config(&self) {
+        // TRANSLATION PACKET CONFIG
+        let jp_font = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer4/OptionButton").get_selected_id();
+        let font_size_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer/LineEdit").get_text();
+        let font_size = font_size_text.to_string().parse::<usize>().unwrap();
+        let romaji = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer2/CheckButton").is_pressed();
+        let min_confidence_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer27/LineEdit").get_text();
+        let mark_confidence_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer28/LineEdit").get_text();
+        let (min_confidence, mark_confidence) = match (min_confidence_text.to_string().parse::<f32>(), mark_confidence_text.to_string().parse::<f32>()) {
+            (Ok(min_confidence), Ok(mark_confidence)) if (0.0..=1.0).contains(&min_confidence) && (0.0..=1.0).contains(&mark_confidence) => (min_confidence, mark_confidence),
+            _ => {
+                self.log_error("Failed to save Translation Packet Config! Confidences must be numbers between 0 and 1.".to_string());
+                return;
+            }
+        };
+        let reading_format = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer37/OptionButton").get_selected_id();
+        let ruby = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer38/CheckButton").is_pressed();
+        let highlight_config = self.get_highlight_config();
+        let pitch_accent = self.get_pitch_style();
+
+        let packet_config = Some(PacketConfig {
+            jp_font,
+            font_size,
+            romaji,
+            min_confidence,
+            mark_confidence,
+            reading_format,
+            ruby,
+            highlight_mode: highlight_config.mode,
+            highlight_style: highlight_config.style,
+            known_jlpt: highlight_config.known_jlpt,
+            known_frequency: highlight_config.known_frequency,
+            pitch_accent,
+        });
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.packet_config = packet_config;
+
+        if self.push_user_settings(&user_settings, "Translation Packet Config") {
+            self.reset_box_conveyor();
+            self.log_message("Translation Packet Config saved!".to_string());
+            self.warn_missing_data();
+        }
+    }
+
+    #[func]
+    fn save_auto_capture(&self) {
+        // AUTO CAPTURE
+        let enabled = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer11/CheckButton").is_pressed();
+        let interval_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer12/LineEdit").get_text();
+        let interval = match interval_text.to_string().parse::<f32>() {
+            Ok(interval) if interval > 0.0 => interval,
+            _ => {
+                self.log_error("Failed to save Auto Capture Config! The interval must be a number of seconds.".to_string());
+                return;
+            }
+        };
+
+        let auto_capture = Some(AutoCaptureConfig {
+            enabled,
+            interval,
+        });
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.auto_capture = auto_capture;
+
+        if self.push_user_settings(&user_settings, "Auto Capture Config") {
+            self.reset_box_conveyor();
+            self.log_message("Auto Capture Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_preprocessing(&self) {
+        // PREPROCESSING
+        let preprocessing = Some(self.get_preprocess_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.preprocessing = preprocessing;
+
+        if self.push_user_settings(&user_settings, "Preprocessing Config") {
+            self.reset_box_conveyor();
+            self.log_message("Preprocessing Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_segmentation(&self) {
+        // SEGMENTATION
+        let segmentation = Some(self.get_segment_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.segmentation = segmentation;
+
+        if self.push_user_settings(&user_settings, "Segmentation Config") {
+            self.reset_box_conveyor();
+            self.log_message("Segmentation Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_furigana(&self) {
+        // FURIGANA
+        let furigana = Some(self.get_furigana_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.furigana = furigana;
+
+        if self.push_user_settings(&user_settings, "Furigana Config") {
+            self.reset_box_conveyor();
+            self.log_message("Furigana Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_normalization(&self) {
+        // NORMALIZATION
+        let normalization = Some(self.get_normalize_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.normalization = normalization;
+
+        if self.push_user_settings(&user_settings, "Normalization Config") {
+            self.reset_box_conveyor();
+            self.log_message("Normalization Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_speakers(&self) {
+        // SPEAKERS
+        let speakers = Some(self.get_speaker_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.speakers = speakers;
+        user_settings.game_profile = Some(self.get_game_profile());
+        user_settings.casts.get_or_insert_with(HashMap::new).insert(self.get_game_profile(), self.get_cast());
+
+        if self.push_user_settings(&user_settings, "Speaker Config") {
+            self.reset_box_conveyor();
+            self.log_message("Speaker Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_anki_config(&self) {
+        // ANKI
+        let anki = Some(self.get_anki_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.anki = anki;
+
+        if self.push_user_settings(&user_settings, "Anki Config") {
+            self.reset_box_conveyor();
+            self.log_message("Anki Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_speech_config(&self) {
+        // SPEECH
+        let speech = Some(self.get_speech_config());
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.speech = speech;
+
+        if self.push_user_settings(&user_settings, "Speech Config") {
+            self.reset_box_conveyor();
+            self.log_message("Speech Config saved!".to_string());
+        }
+    }
+
+    #[func]
+    fn save_text_rules(&self) {
+        // TEXT RULES
+        let rules_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit2").get_text().to_string();
+        let game_rules = match rules::parse_rules(&rules_text).and_then(|game_rules| RuleSet::new(game_rules.clone()).map(|_| game_rules)) {
+            Ok(game_rules) => game_rules,
+            Err(error) => {
+                self.log_error(format!("Failed to save Text Rules! {}", error));
+                return;
+            }
+        };
+
+        let mut user_settings = self.pull_user_settings();
+        user_settings.game_profile = Some(self.get_game_profile());
+        user_settings.text_rules.get_or_insert_with(HashMap::new).insert(self.get_game_profile(), game_rules);
+
+        if self.push_user_settings(&user_settings, "Text Rules") {
+            self.reset_box_conveyor();
+            self.log_message(format!("Text Rules for {} saved!", self.get_game_profile()));
+        }
+    }
+
+    // The portal restore token is saved as soon as it changes, without a save button
+    fn save_portal_token(&mut self) {
+        let portal_token = self.portal.lock().unwrap().restore_token.clone();
+        if portal_token == self.saved_portal_token {
+            return;
+        }
+        let mut user_settings = self.pull_user_settings();
+        user_settings.wayland_portal = portal_token.clone().map(|restore_token| WaylandPortal { restore_token });
+        // Only tried once per token, so a failed write is not reported on every capture
+        self.push_user_settings(&user_settings, "Wayland Portal Token");
+        self.saved_portal_token = portal_token;
+    }
+
+    fn push_user_settings(&self, user_settings: &UserSettings, label: &str) -> bool {
+        // PUSH
+        let Ok(serialized) = toml::to_string(user_settings) else {
+            self.log_error(format!("Failed to save {}! You may have used invalid values.", label));
+            return false;
+        };
+        let Some(mut file) = FileAccess::open("user://user_settings.toml", ModeFlags::WRITE) else {
+            self.log_error(format!("Failed to save {}! Could not open user://user_settings.toml for writing.", label));
+            return false;
+        };
+        file.store_string(&serialized);
+        file.close();
+        true
+    }
+
+    fn pull_user_settings(&self) -> UserSettings {
+        // PULL
+        // A missing or unreadable file starts over from the defaults
+        read_user_settings().unwrap_or_default()
+    }
+
+    fn load_user_settings(&mut self) {
+        match read_user_settings() {
+            Ok(user_settings) => {
+                // SET CREDENTIALS
+                if let Some(user_credentials) = user_settings.user_credentials {
+                    let mut gcloud_token = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit");
+                    let mut project_id = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer8/LineEdit");
+                    let mut deepl_token = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer9/LineEdit");
+                    gcloud_token.set_text(&user_credentials.gcloud_token);
+                    project_id.set_text(&user_credentials.project_id);
+                    deepl_token.set_text(&user_credentials.deepl_token);
+                }
                 // SET READING AREA
                 if let Some(reading_area) = user_settings.reading_area {
                     let mut up = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer7/LineEdit");
@@ -699,6 +1665,132 @@ impl System {
                     jp_font.select(packet_config.jp_font);
                     font_size.set_text(&packet_config.font_size.to_string());
                     romaji.set_pressed(packet_config.romaji);
+                    let mut min_confidence = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer27/LineEdit");
+                    let mut mark_confidence = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer28/LineEdit");
+                    min_confidence.set_text(&packet_config.min_confidence.to_string());
+                    mark_confidence.set_text(&packet_config.mark_confidence.to_string());
+                    let mut reading_format = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer37/OptionButton");
+                    reading_format.select(packet_config.reading_format);
+                    let mut ruby = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer38/CheckButton");
+                    ruby.set_pressed(packet_config.ruby);
+                    let mut highlight_mode = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer39/OptionButton");
+                    let mut highlight_style = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer40/OptionButton");
+                    let mut known_jlpt = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer41/OptionButton");
+                    let mut known_frequency = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer42/LineEdit");
+                    highlight_mode.select(packet_config.highlight_mode);
+                    highlight_style.select(packet_config.highlight_style);
+                    known_jlpt.select(packet_config.known_jlpt);
+                    known_frequency.set_text(&packet_config.known_frequency.to_string());
+                    let mut pitch_accent = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer57/OptionButton");
+                    pitch_accent.select(packet_config.pitch_accent);
+                }
+                // SET AUTO CAPTURE
+                if let Some(auto_capture) = user_settings.auto_capture {
+                    let mut enabled = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer11/CheckButton");
+                    let mut interval = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer12/LineEdit");
+                    enabled.set_pressed(auto_capture.enabled);
+                    interval.set_text(&auto_capture.interval.to_string());
+                }
+                // SET PREPROCESSING
+                if let Some(preprocessing) = user_settings.preprocessing {
+                    let mut upscale = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer13/LineEdit");
+                    let mut upscale_filter = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer14/OptionButton");
+                    let mut grayscale = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer15/CheckButton");
+                    let mut contrast = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer16/LineEdit");
+                    let mut threshold = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer17/LineEdit");
+                    let mut invert = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer18/CheckButton");
+                    let mut denoise = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer19/CheckButton");
+                    let mut color_key = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer20/LineEdit");
+                    let mut color_key_tolerance = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer20/LineEdit2");
+                    upscale.set_text(&preprocessing.upscale.to_string());
+                    upscale_filter.select(preprocessing.upscale_filter);
+                    grayscale.set_pressed(preprocessing.grayscale);
+                    contrast.set_text(&preprocessing.contrast.to_string());
+                    threshold.set_text(&preprocessing.threshold.to_string());
+                    invert.set_pressed(preprocessing.invert);
+                    denoise.set_pressed(preprocessing.denoise);
+                    color_key.set_text(&preprocess::format_color_key(preprocessing.color_key));
+                    color_key_tolerance.set_text(&preprocessing.color_key_tolerance.to_string());
+                }
+                // SET SEGMENTATION
+                if let Some(segmentation) = user_settings.segmentation {
+                    let mut merge = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer21/CheckButton");
+                    let mut merge_distance = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer22/LineEdit");
+                    let mut split = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer23/CheckButton");
+                    let mut split_gap = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer24/LineEdit");
+                    merge.set_pressed(segmentation.merge);
+                    merge_distance.set_text(&segmentation.merge_distance.to_string());
+                    split.set_pressed(segmentation.split);
+                    split_gap.set_text(&segmentation.split_gap.to_string());
+                }
+                // SET FURIGANA
+                if let Some(furigana) = user_settings.furigana {
+                    let mut strip = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer25/CheckButton");
+                    let mut use_as_reading = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer26/CheckButton");
+                    strip.set_pressed(furigana.strip);
+                    use_as_reading.set_pressed(furigana.use_as_reading);
+                }
+                // SET NORMALIZATION
+                if let Some(normalization) = user_settings.normalization {
+                    let mut nfkc = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer30/CheckButton");
+                    let mut confusables = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer31/CheckButton");
+                    let mut ellipsis = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer32/CheckButton");
+                    let mut dashes = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer33/CheckButton");
+                    let mut stray_marks = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer34/CheckButton");
+                    nfkc.set_pressed(normalization.nfkc);
+                    confusables.set_pressed(normalization.confusables);
+                    ellipsis.set_pressed(normalization.ellipsis);
+                    dashes.set_pressed(normalization.dashes);
+                    stray_marks.set_pressed(normalization.stray_marks);
+                }
+                // SET SPEAKERS
+                if let Some(speakers) = user_settings.speakers {
+                    let mut patterns = self.base().get_node_as::<CheckButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer35/CheckButton");
+                    let mut name_region = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer36/LineEdit");
+                    patterns.set_pressed(speakers.patterns);
+                    name_region.set_text(&speaker::format_name_region(speakers.name_region));
+                }
+                // SET KNOWN WORDS
+                if let Some(known_words) = user_settings.known_words {
+                    self.known_words = known_words.into_iter().collect();
+                    let mut known_words_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit4");
+                    known_words_text.set_text(&difficulty::format_known_words(&self.known_words));
+                }
+                // SET ANKI
+                if let Some(anki) = user_settings.anki {
+                    let line_edit = |row: &str| self.base().get_node_as::<LineEdit>(&format!("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/{}/LineEdit", row));
+                    line_edit("HBoxContainer43").set_text(&anki.url);
+                    line_edit("HBoxContainer44").set_text(&anki.deck);
+                    line_edit("HBoxContainer45").set_text(&anki.note_type);
+                    line_edit("HBoxContainer46").set_text(&anki.fields.sentence);
+                    line_edit("HBoxContainer47").set_text(&anki.fields.reading);
+                    line_edit("HBoxContainer48").set_text(&anki.fields.translation);
+                    line_edit("HBoxContainer49").set_text(&anki.fields.screenshot);
+                    line_edit("HBoxContainer50").set_text(&anki.fields.word);
+                    line_edit("HBoxContainer51").set_text(&anki.fields.meaning);
+                }
+                // SET SPEECH
+                if let Some(speech) = user_settings.speech {
+                    let mut engine = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer52/OptionButton");
+                    let mut url = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer53/LineEdit");
+                    let mut voice = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer54/LineEdit");
+                    let mut speed = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer55/LineEdit");
+                    let mut dictionary = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer56/LineEdit");
+                    engine.select(speech.engine);
+                    url.set_text(&speech.url);
+                    voice.set_text(&speech.voice);
+                    speed.set_text(&speech.speed.to_string());
+                    dictionary.set_text(&speech.dictionary);
+                }
+                // SET GAME PROFILE
+                if let Some(game_profile) = user_settings.game_profile {
+                    let mut profile = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer58/LineEdit");
+                    profile.set_text(&game_profile);
+                }
+                // SET WAYLAND PORTAL
+                if let Some(wayland_portal) = user_settings.wayland_portal {
+                    self.portal.lock().unwrap().restore_token = Some(wayland_portal.restore_token.clone());
+                    self.saved_portal_token = Some(wayland_portal.restore_token);
                 }
             },
             Err(error) => {
@@ -715,6 +1807,31 @@ impl System {
         vbox_error.set_visible(true);
     }
 
+    fn log_message(&self, message_string: String) {
+        self.clear_errors();
+        let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
+        console.set_text(&message_string);
+    }
+
+    // VETS does not ship its dictionaries, so a missing one is named before its feature quietly does nothing
+    fn warn_missing_data(&self) {
+        let highlight_mode = self.get_highlight_config().mode;
+        let data_files = [
+            (JMDICT_PATH, "word lookup", true),
+            (KANJIDIC_PATH, "kanji details", true),
+            (JLPT_PATH, "JLPT highlighting", highlight_mode == difficulty::HIGHLIGHT_JLPT),
+            (FREQUENCY_PATH, "frequency highlighting", highlight_mode == difficulty::HIGHLIGHT_FREQUENCY),
+            (ACCENTS_PATH, "pitch accents", self.get_pitch_style() != pitch::PITCH_OFF),
+        ];
+        let missing: Vec<String> = data_files.iter()
+            .filter(|(path, _, is_needed)| *is_needed && !FileAccess::file_exists(*path))
+            .map(|(path, feature, _)| format!("{} ({})", path.replace("res://", "godot/"), feature))
+            .collect();
+        if !missing.is_empty() {
+            self.log_warning(format!("Missing {}. VETS does not ship these files: see Dictionaries in the README to download them.", missing.join(", ")));
+        }
+    }
+
     fn log_warning(&self, warning_string: String) {
         self.reset_box_conveyor();
         let mut vbox_warning = self.base().get_node_as::<VBoxContainer>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/vbox_warning");
@@ -734,22 +1851,94 @@ impl System {
         console_warning.clear();
     }
 
-    fn make_packets(&self, mut gui: Gd<sandGUI>, packets: Vec<TranslationPacket>) {
-        let mut vbox = gui.get_node_as::<VBoxContainer>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Reader/PanelContainer/ScrollContainer/VBoxContainer");
-        reset(vbox.clone().upcast());
+    // Packets still on screen from the last capture stay in place,
+    // only new lines are faded in
+    fn make_packets(&mut self, mut gui: Gd<sandGUI>, packets: Vec<TranslationPacket>) {
+        let mut vbox = gui.get_node_as::<VBoxContainer>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Reader/PanelContainer/VBoxContainer/ScrollContainer/VBoxContainer");
         godot_print!("Packets found: {}", packets.len());
-        for packet in packets {
+        // The placeholder text goes with the first packets
+        if let Some(mut placeholder) = vbox.get_node_or_null("MarginContainer") {
+            vbox.remove_child(&placeholder);
+            placeholder.queue_free();
+        }
+        // -- OCR REGIONS --
+        let screen_textrect = self.base().get_node_as::<TextureRect>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Reader/PanelContainer/VBoxContainer/screen_textrect");
+        // The preview shows the preprocessed capture, the regions are in the crop's coordinates
+        let texture_size = screen_textrect.get_texture().map(|texture| texture.get_size() / self.last_upscale as f32).unwrap_or(Vector2::ONE);
+        let mut overlay = screen_textrect.get_node_as::<sandOverlay>("overlay");
+        overlay.bind_mut().set_regions(packets.iter().map(|packet| packet.bounds.clone()).collect(), texture_size);
+        if let Some(mut packet) = self.highlighted_packet.and_then(|i| self.packet_nodes.get(i).cloned()) {
+            packet.set_self_modulate(Color::WHITE);
+        }
+        self.highlighted_packet = None;
+        let mut old_nodes: Vec<(String, Gd<PanelContainer>)> = self.packet_keys.drain(..).zip(self.packet_nodes.drain(..)).collect();
+        self.previous_packets = packets.clone();
+        for (i, packet) in packets.into_iter().enumerate() {
+            let key = packet.key();
+            if let Some(old_index) = old_nodes.iter().position(|(old_key, _)| *old_key == key) {
+                let (_, mut translation_packet) = old_nodes.remove(old_index);
+                // Same line, but a re-read may have translated it differently
+                let mut eng_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/engtext_container/engtext");
+                eng_text.set_text(&packet.eng_text);
+                self.render_jp_text(&translation_packet, &packet);
+                self.post_process_packet(&mut translation_packet);
+                vbox.move_child(&translation_packet, i as i32);
+                self.packet_nodes.push(translation_packet);
+                self.packet_keys.push(key);
+                continue;
+            }
             let mut translation_packet = load::<PackedScene>("res://translation_packet.tscn").instantiate_as::<PanelContainer>();
-            let mut jp_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jptext_container/jptext");
-            let mut jp_read = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jpread_container/jpread");
             let mut eng_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/engtext_container/engtext");
-            jp_text.set_text(&packet.jp_text.clone());
-            jp_read.set_text(&packet.jp_read);
+            let jp_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jptext_container/jptext");
+            self.connect_word_lookup(&jp_text, &translation_packet);
+            let mut mine_button = translation_packet.get_node_as::<Button>("VBoxContainer/action_bar/mine_button");
+            mine_button.connect("pressed", &Callable::from_object_method(&self.to_gd(), "mine_packet").bindv(&varray![translation_packet.clone()]));
+            let mut play_button = translation_packet.get_node_as::<Button>("VBoxContainer/action_bar/play_button");
+            play_button.connect("pressed", &Callable::from_object_method(&self.to_gd(), "speak_packet").bindv(&varray![translation_packet.clone()]));
+            self.render_jp_text(&translation_packet, &packet);
             eng_text.set_text(&packet.eng_text);
+            if let Some(speaker_name) = &packet.speaker {
+                let mut speaker_container = translation_packet.get_node_as::<PanelContainer>("VBoxContainer/speaker_container");
+                let mut speaker_label = speaker_container.get_node_as::<RichTextLabel>("speaker");
+                if speaker_name.eng.is_empty() {
+                    speaker_label.set_text(&speaker_name.jp);
+                } else {
+                    speaker_label.set_text(&format!("{}  [color=#777777]{}", speaker_name.jp, speaker_name.eng));
+                }
+                speaker_container.set_visible(true);
+            }
             self.post_process_packet(&mut translation_packet);
             make_child(&mut vbox, translation_packet.clone().upcast());
+            vbox.move_child(&translation_packet, i as i32);
+            self.packet_nodes.push(translation_packet.clone());
+            self.packet_keys.push(key);
             gui.bind_mut().fade_in(translation_packet.upcast());
         }
+        // Lines that left the screen
+        for (_, mut translation_packet) in old_nodes {
+            translation_packet.queue_free();
+        }
+    }
+
+    // Hovering a packet outlines its region on the preview, and hovering
+    // a region on the preview brightens its packet
+    fn highlight_regions(&mut self) {
+        let mut overlay = self.base().get_node_as::<sandOverlay>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Reader/PanelContainer/VBoxContainer/screen_textrect/overlay");
+        let hovered_packet = self.packet_nodes.iter().position(|packet| {
+            packet.is_visible_in_tree() && packet.get_global_rect().contains_point(packet.get_global_mouse_position())
+        });
+        let hovered = hovered_packet.or(overlay.bind().region_at_mouse());
+        if hovered == self.highlighted_packet {
+            return;
+        }
+        if let Some(mut packet) = self.highlighted_packet.and_then(|i| self.packet_nodes.get(i).cloned()) {
+            packet.set_self_modulate(Color::WHITE);
+        }
+        if let Some(mut packet) = hovered.and_then(|i| self.packet_nodes.get(i).cloned()) {
+            packet.set_self_modulate(Color::from_rgba(1.4, 1.4, 1.4, 1.0));
+        }
+        overlay.bind_mut().highlight(hovered);
+        self.highlighted_packet = hovered;
     }
 
     fn post_process_packet(&self, translation_packet: &mut Gd<PanelContainer>) {
@@ -758,14 +1947,17 @@ impl System {
         let mut jp_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jptext_container/jptext");
         let mut jp_read = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jpread_container/jpread");
         let mut eng_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/engtext_container/engtext");
+        let mut speaker = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/speaker_container/speaker");
         // -- JP FONT --
         let jp_font = gui.get_node_as::<OptionButton>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer4/OptionButton").get_selected_id();
         if jp_font == 0 {
             let font_dotgothic = load::<Font>("res://menu/font/DotGothic16-Regular.ttf");
             jp_text.add_theme_font_override("normal_font", &font_dotgothic);
+            speaker.add_theme_font_override("normal_font", &font_dotgothic);
         } else if jp_font == 1 {
             let font_shippori = load::<Font>("res://menu/font/ShipporiMincho-Regular.ttf");
             jp_text.add_theme_font_override("normal_font", &font_shippori);
+            speaker.add_theme_font_override("normal_font", &font_shippori);
         }
         // -- FONT SIZE --
         let mut font_size_box = gui.get_node_as::<LineEdit>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer/LineEdit");
@@ -774,18 +1966,483 @@ impl System {
         jp_text.add_theme_font_size_override("normal_font_size", font_size);
         jp_read.add_theme_font_size_override("normal_font_size", font_size);
         eng_text.add_theme_font_size_override("normal_font_size", font_size);
+        speaker.add_theme_font_size_override("normal_font_size", font_size);
+        // -- RUBY --
+        let mut ruby_flow = translation_packet.get_node_as::<HFlowContainer>("VBoxContainer/jptext_container/ruby_flow");
+        for cell in ruby_flow.get_children().iter_shared() {
+            let mut ruby = cell.get_node_as::<Label>("ruby");
+            let mut base = cell.get_node_as::<RichTextLabel>("base");
+            if let Some(font) = jp_text.get_theme_font("normal_font") {
+                ruby.add_theme_font_override("font", &font);
+                base.add_theme_font_override("normal_font", &font);
+            }
+            ruby.add_theme_font_size_override("font_size", (font_size / 2).max(8));
+            base.add_theme_font_size_override("normal_font_size", font_size);
+        }
         // -- ROMAJI --
         let romaji = gui.get_node_as::<CheckButton>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer2/CheckButton").is_pressed();
-        if romaji == true {
-            jp_read.set_visible(true);
+        let ruby = gui.get_node_as::<CheckButton>("MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer38/CheckButton").is_pressed();
+        // Ruby takes the place of both the plain line and the reading line
+        jp_read.set_visible(romaji && !ruby);
+        jp_text.set_visible(!(romaji && ruby));
+        ruby_flow.set_visible(romaji && ruby);
+    }
+
+    // ________________________________________
+    // Fill the Japanese line, the reading line
+    // and the ruby cells of a packet. Which of
+    // them show is left to post_process_packet
+    // ----------------------------------------
+    fn render_jp_text(&self, translation_packet: &Gd<PanelContainer>, packet: &TranslationPacket) {
+        let mark_confidence_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer28/LineEdit").get_text();
+        let mark_confidence = mark_confidence_text.to_string().parse::<f32>().unwrap_or(DEFAULT_MARK_CONFIDENCE);
+        let reading_format = self.get_reading_format();
+        // -- HIGHLIGHTS --
+        // Colour by where each word starts
+        let highlight_config = self.get_highlight_config();
+        let jlpt_list = self.jlpt_list.lock().unwrap();
+        let frequency_list = self.frequency_list.lock().unwrap();
+        let highlights: HashMap<usize, &str> = packet.words.iter()
+            .filter(|word| !word.is_known(&self.known_words))
+            .filter_map(|word| difficulty::highlight_color(word, &highlight_config, jlpt_list.as_ref(), frequency_list.as_ref()).map(|color| (word.start, color)))
+            .collect();
+        drop(jlpt_list);
+        drop(frequency_list);
+        // -- PITCH ACCENT --
+        let pitch_style = self.get_pitch_style();
+        let accent_dictionary = self.accent_dictionary.lock().unwrap();
+        let accents: Vec<Option<u32>> = match accent_dictionary.as_ref() {
+            Some(accent_dictionary) if pitch_style != pitch::PITCH_OFF => packet.words.iter().map(|word| pitch::word_accent(word, accent_dictionary)).collect(),
+            _ => Vec::new(),
+        };
+        drop(accent_dictionary);
+        let mut jp_text = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jptext_container/jptext");
+        let mut jp_read = translation_packet.get_node_as::<RichTextLabel>("VBoxContainer/jpread_container/jpread");
+        jp_text.set_text(&link_words(&packet.jp_text, &packet.confidences, mark_confidence, &packet.words, &highlights, highlight_config.style));
+        jp_read.set_text(&reading::format_reading(&packet.jp_text, &packet.jp_read, &packet.words, &self.known_words, &accents, pitch_style, reading_format));
+        // -- RUBY --
+        // One cell per kanji word with its reading on top, and one per other
+        // character so the line still wraps anywhere like plain Japanese text
+        let mut ruby_flow = translation_packet.get_node_as::<HFlowContainer>("VBoxContainer/jptext_container/ruby_flow");
+        for mut cell in ruby_flow.get_children().iter_shared() {
+            ruby_flow.remove_child(&cell);
+            cell.queue_free();
+        }
+        let mut offset = 0;
+        for segment in reading::segments(&packet.jp_text, &packet.jp_read, &packet.words, &self.known_words) {
+            let mut pieces: Vec<(String, String)> = Vec::new();
+            match &segment.reading {
+                Some(segment_reading) => pieces.push((segment.text.clone(), reading::ruby_reading(segment_reading, reading_format))),
+                None => pieces.extend(segment.text.chars().map(|c| (c.to_string(), String::new()))),
+            }
+            for (text, ruby_text) in pieces {
+                let length = text.chars().count();
+                let confidences = packet.confidences.get(offset..offset + length).unwrap_or(&[]);
+                offset += length;
+                let mut cell = VBoxContainer::new_alloc();
+                cell.add_theme_constant_override("separation", 0);
+                let mut ruby = Label::new_alloc();
+                ruby.set_name("ruby");
+                // A blank keeps bare characters on the same baseline as words with ruby
+                ruby.set_text(if ruby_text.is_empty() { " " } else { &ruby_text });
+                ruby.set_horizontal_alignment(HorizontalAlignment::CENTER);
+                ruby.add_theme_color_override("font_color", Color::from_rgba(0.709804, 0.690196, 0.678431, 1.0));
+                let mut base = RichTextLabel::new_alloc();
+                base.set_name("base");
+                base.set_use_bbcode(true);
+                base.set_fit_content(true);
+                base.set_autowrap_mode(AutowrapMode::OFF);
+                base.set_h_size_flags(SizeFlags::SHRINK_CENTER);
+                base.set_meta_underline(false);
+                let start = word_start(&packet.words, offset - length);
+                let mut marked_text = mark_low_confidence(&text, confidences, mark_confidence);
+                if let Some(color) = highlights.get(&start) {
+                    marked_text = difficulty::highlight(&marked_text, color, highlight_config.style);
+                }
+                base.set_text(&format!("[url={}]{}[/url]", start, marked_text));
+                self.connect_word_lookup(&base, translation_packet);
+                make_child(&mut cell, ruby.upcast());
+                make_child(&mut cell, base.upcast());
+                make_child(&mut ruby_flow, cell.upcast());
+            }
+        }
+    }
+
+    // ---- WORD LOOKUP ----
+    fn connect_word_lookup(&self, label: &Gd<RichTextLabel>, translation_packet: &Gd<PanelContainer>) {
+        let mut label = label.clone();
+        let system = self.to_gd();
+        label.connect("meta_hover_started", &Callable::from_object_method(&system, "hover_word").bindv(&varray![translation_packet.clone()]));
+        label.connect("meta_clicked", &Callable::from_object_method(&system, "click_word").bindv(&varray![translation_packet.clone()]));
+        label.connect("meta_hover_ended", &Callable::from_object_method(&system, "unhover_word"));
+        label.connect("gui_input", &Callable::from_object_method(&system, "select_kanji").bindv(&varray![label.clone()]));
+    }
+
+    #[func]
+    fn hover_word(&mut self, meta: Variant, translation_packet: Gd<PanelContainer>) {
+        if !self.popup_pinned {
+            self.show_word_popup(meta, translation_packet);
+        }
+    }
+
+    // A clicked word keeps its popup until the next click elsewhere
+    #[func]
+    fn click_word(&mut self, meta: Variant, translation_packet: Gd<PanelContainer>) {
+        self.popup_pinned = true;
+        self.show_word_popup(meta, translation_packet);
+    }
+
+    #[func]
+    fn unhover_word(&mut self, _meta: Variant) {
+        if !self.popup_pinned {
+            self.hide_word_popup();
+        }
+    }
+
+    fn show_word_popup(&mut self, meta: Variant, translation_packet: Gd<PanelContainer>) {
+        let packet_index = self.packet_nodes.iter().position(|node| *node == translation_packet);
+        let Some(packet) = packet_index.and_then(|i| self.previous_packets.get(i)) else {
+            return;
+        };
+        let Ok(start) = meta.to_string().parse::<usize>() else {
+            return;
+        };
+        let text: String = packet.jp_text.chars().skip(start).collect();
+        let base_form = packet.words.iter()
+            .find(|word| word.start == start && !word.is_punctuation())
+            .map(|word| word.base_form.clone());
+        self.popup_word = base_form.clone();
+        self.popup_packet = packet_index;
+        self.popup_mined_word = None;
+        self.update_known_button();
+        let mut popup = self.base().get_node_as::<PanelContainer>("sandGUI/word_popup");
+        let mut entries = popup.get_node_as::<RichTextLabel>("VBoxContainer/ScrollContainer/word_entries");
+        match self.jmdict.lock().unwrap().as_ref() {
+            Some(jmdict) => {
+                let results = jmdict.lookup(&text, base_form.as_deref());
+                if results.is_empty() {
+                    entries.set_text(&format!("[color=#777777]No entries for {}[/color]", text.chars().next().unwrap_or(' ')));
+                } else {
+                    entries.set_text(&dictionary::format_results(jmdict, &results));
+                }
+                // The first entry is the one a card is mined with
+                self.popup_mined_word = results.first().map(|result| {
+                    let kana = result.entry.kana.first().map(|spelling| spelling.text.clone()).unwrap_or_default();
+                    let glosses = result.entry.sense.first().map(|sense| sense.gloss.iter().map(|gloss| gloss.text.as_str()).collect::<Vec<&str>>().join("; "));
+                    MinedWord {
+                        word: result.entry.kanji.first().map(|spelling| spelling.text.clone()).unwrap_or_else(|| kana.clone()),
+                        reading: kana,
+                        meaning: glosses.unwrap_or_default(),
+                    }
+                });
+            },
+            None => entries.set_text("[color=#777777]No dictionary loaded. Place jmdict-eng.json in the dict folder to look up words.[/color]"),
+        }
+        // Next to the mouse, but kept inside the window
+        let viewport_size = popup.get_viewport_rect().size;
+        let popup_size = popup.get_combined_minimum_size();
+        let mouse_position = popup.get_global_mouse_position();
+        let x = (mouse_position.x + 16.0).min(viewport_size.x - popup_size.x).max(0.0);
+        let y = (mouse_position.y + 16.0).min(viewport_size.y - popup_size.y).max(0.0);
+        popup.set_global_position(Vector2::new(x, y));
+        popup.set_visible(true);
+    }
+
+    // Clicking a kanji in the word popup opens its breakdown
+    #[func]
+    fn open_kanji(&mut self, meta: Variant) {
+        if let Some(literal) = meta.to_string().chars().next() {
+            self.show_kanji_panel(literal);
+        }
+    }
+
+    // Selecting text in a packet's Japanese line opens the first kanji of the
+    // selection. The selection is cleared, so the next click can close the panel
+    #[func]
+    fn select_kanji(&mut self, event: Gd<InputEvent>, mut jp_text: Gd<RichTextLabel>) {
+        if !event.is_action_released("mouse_click") {
+            return;
+        }
+        let selected_kanji = jp_text.get_selected_text().to_string().chars().find(|c| reading::is_kanji(*c));
+        if let Some(literal) = selected_kanji {
+            jp_text.deselect();
+            self.show_kanji_panel(literal);
+        }
+    }
+
+    fn show_kanji_panel(&mut self, literal: char) {
+        let mut kanji_panel = self.base().get_node_as::<PanelContainer>("sandGUI/kanji_panel");
+        let mut kanji_info = kanji_panel.get_node_as::<RichTextLabel>("VBoxContainer/kanji_info");
+        match self.kanjidic.lock().unwrap().as_ref() {
+            Some(kanjidic) => match kanjidic.get(literal) {
+                Some(kanji) => kanji_info.set_text(&kanji::format_kanji(kanji)),
+                None => kanji_info.set_text(&format!("[font_size=48]{}[/font_size]\n[color=#777777]Not in KANJIDIC2[/color]", literal)),
+            },
+            None if !FileAccess::file_exists(KANJIDIC_PATH) => kanji_info.set_text("[color=#777777]No kanji dictionary installed. Download kanjidic2-en.json and save it as godot/dict/kanjidic2-en.json to see kanji details.[/color]"),
+            None => kanji_info.set_text("[color=#777777]The kanji dictionary is still loading, or could not be read.[/color]"),
+        }
+        // Beside the word popup when it is open, otherwise by the mouse
+        let popup = self.base().get_node_as::<PanelContainer>("sandGUI/word_popup");
+        let viewport_size = kanji_panel.get_viewport_rect().size;
+        let panel_size = kanji_panel.get_combined_minimum_size();
+        let anchor = if popup.is_visible() {
+            let popup_rect = popup.get_global_rect();
+            Vector2::new(popup_rect.position.x + popup_rect.size.x + 8.0, popup_rect.position.y)
         } else {
-            jp_read.set_visible(false);
+            kanji_panel.get_global_mouse_position() + Vector2::new(16.0, 16.0)
+        };
+        let x = anchor.x.min(viewport_size.x - panel_size.x).max(0.0);
+        let y = anchor.y.min(viewport_size.y - panel_size.y).max(0.0);
+        kanji_panel.set_global_position(Vector2::new(x, y));
+        kanji_panel.set_visible(true);
+    }
+
+    fn hide_word_popup(&mut self) {
+        let mut popup = self.base().get_node_as::<PanelContainer>("sandGUI/word_popup");
+        popup.set_visible(false);
+        self.popup_pinned = false;
+    }
+
+    // ---- KNOWN WORDS ----
+    // Only tokenized lines have words to mark, so the button waits for one
+    fn update_known_button(&self) {
+        let mut known_button = self.base().get_node_as::<Button>("sandGUI/word_popup/VBoxContainer/HBoxContainer/known_button");
+        match &self.popup_word {
+            Some(word) if self.known_words.contains(word) => known_button.set_text(&format!("Forget {}", word)),
+            Some(word) => known_button.set_text(&format!("I know {}", word)),
+            None => known_button.set_text("I know this word"),
+        }
+        known_button.set_disabled(self.popup_word.is_none());
+    }
+
+    #[func]
+    fn toggle_known_word(&mut self) {
+        let Some(word) = self.popup_word.clone() else {
+            return;
+        };
+        if !self.known_words.remove(&word) {
+            self.known_words.insert(word);
+        }
+        self.update_known_button();
+        self.push_known_words();
+    }
+
+    #[func]
+    fn save_known_words(&mut self) {
+        let known_words_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit4").get_text().to_string();
+        self.known_words = difficulty::parse_known_words(&known_words_text);
+        self.push_known_words();
+    }
+
+    #[func]
+    fn open_import_words_dialog(&self) {
+        let mut import_dialog = self.base().get_node_as::<FileDialog>("ImportWordsDialog");
+        import_dialog.popup_centered();
+    }
+
+    // Imported words join the list rather than replace it
+    #[func]
+    fn import_known_words(&mut self, path: GString) {
+        match std::fs::read_to_string(path.to_string()) {
+            Ok(contents) => {
+                self.known_words.extend(difficulty::parse_known_words(&contents));
+                self.push_known_words();
+            },
+            Err(error) => self.log_error(format!("Failed to import known words: {}", error)),
+        }
+    }
+
+    #[func]
+    fn open_export_words_dialog(&self) {
+        let mut export_dialog = self.base().get_node_as::<FileDialog>("ExportWordsDialog");
+        export_dialog.set_current_file("known_words.txt");
+        export_dialog.popup_centered();
+    }
+
+    #[func]
+    fn export_known_words(&mut self, path: GString) {
+        self.clear_errors();
+        match std::fs::write(path.to_string(), difficulty::format_known_words(&self.known_words) + "\n") {
+            Ok(()) => {
+                let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
+                console.set_text(&format!("{} known words exported to {}!", self.known_words.len(), path));
+            },
+            Err(error) => self.log_error(format!("Failed to export known words: {}", error)),
+        }
+    }
+
+    // The list is saved on every change. Refreshing the preview packet also
+    // re-renders the packets on screen, so readings of known words drop at once.
+    // The "I know" button calls this mid-reading, so the box conveyor is left alone
+    fn push_known_words(&self) {
+        let mut known_words_text = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/TextEdit4");
+        known_words_text.set_text(&difficulty::format_known_words(&self.known_words));
+        self.refresh_preview_packet();
+
+        let mut user_settings = self.pull_user_settings();
+        let mut known_words: Vec<String> = self.known_words.iter().cloned().collect();
+        known_words.sort();
+        user_settings.known_words = Some(known_words);
+
+        if self.push_user_settings(&user_settings, "Known Words") {
+            self.log_message(format!("Known Words saved! {} words.", self.known_words.len()));
+        }
+    }
+
+    // ---- ANKI ----
+    #[func]
+    fn mine_packet(&mut self, translation_packet: Gd<PanelContainer>) {
+        if let Some(i) = self.packet_nodes.iter().position(|node| *node == translation_packet) {
+            self.mine(i, None);
+        }
+    }
+
+    // Mined from the popup, the card also gets the word and its first meaning
+    #[func]
+    fn mine_word(&mut self) {
+        let Some(i) = self.popup_packet else {
+            return;
+        };
+        self.mine(i, self.popup_mined_word.clone());
+    }
+
+    fn mine(&mut self, packet_index: usize, mined_word: Option<MinedWord>) {
+        let Some(packet) = self.previous_packets.get(packet_index) else {
+            return;
+        };
+        let capture = self.packets_capture_id.and_then(|id| self.history.get(id));
+        let note = MinedNote {
+            sentence: packet.jp_text.clone(),
+            // Cards get every reading, known words included
+            reading: anki::anki_furigana(&reading::segments(&packet.jp_text, &packet.jp_read, &packet.words, &HashSet::new())),
+            translation: packet.eng_text.clone(),
+            word: mined_word,
+            screenshot: capture.map(|entry| entry.png_bytes.clone()),
+            screenshot_name: capture.map(|entry| format!("vets_capture_{}.png", entry.id)).unwrap_or_default(),
+        };
+        let anki_config = self.get_anki_config();
+        let anki_queue_clone = Arc::clone(&self.anki_queue);
+        let mut console = self.base().get_node_as::<TextEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/PanelContainer/VBoxContainer/console_text");
+        console.set_text("Sending card to Anki...");
+        thread::spawn(move || {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let result = anki::add_note(&anki_config, &note).await
+                    .map(|_| format!("Card added to {}!", anki_config.deck));
+                anki_queue_clone.lock().unwrap().push(result);
+            });
+        });
+    }
+
+    fn get_anki_config(&self) -> AnkiConfig {
+        let line_edit = |row: &str| self.base().get_node_as::<LineEdit>(&format!("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/{}/LineEdit", row)).get_text().to_string().trim().to_string();
+        AnkiConfig {
+            url: line_edit("HBoxContainer43"),
+            deck: line_edit("HBoxContainer44"),
+            note_type: line_edit("HBoxContainer45"),
+            fields: FieldMapping {
+                sentence: line_edit("HBoxContainer46"),
+                reading: line_edit("HBoxContainer47"),
+                translation: line_edit("HBoxContainer48"),
+                screenshot: line_edit("HBoxContainer49"),
+                word: line_edit("HBoxContainer50"),
+                meaning: line_edit("HBoxContainer51"),
+            },
         }
     }
 
+    // ---- SPEECH ----
+    #[func]
+    fn speak_packet(&mut self, translation_packet: Gd<PanelContainer>) {
+        let Some(packet) = self.packet_nodes.iter().position(|node| *node == translation_packet).and_then(|i| self.previous_packets.get(i)) else {
+            return;
+        };
+        let jp_text = packet.jp_text.clone();
+        let speech_config = self.get_speech_config();
+        let speech_queue_clone = Arc::clone(&self.speech_queue);
+        thread::spawn(move || {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let result = tts::synthesize(&speech_config, &jp_text).await;
+                speech_queue_clone.lock().unwrap().push(result);
+            });
+        });
+    }
+
+    // A new line cuts off the one still playing
+    fn play_speech(&self, wav_bytes: &[u8]) {
+        let wav = match tts::parse_wav(wav_bytes) {
+            Ok(wav) => wav,
+            Err(error) => {
+                self.log_error(error);
+                return;
+            }
+        };
+        let mut stream = AudioStreamWav::new_gd();
+        stream.set_format(if wav.is_16_bit { Format::FORMAT_16_BITS } else { Format::FORMAT_8_BITS });
+        stream.set_mix_rate(wav.sample_rate as i32);
+        stream.set_stereo(wav.is_stereo);
+        stream.set_data(&PackedByteArray::from(wav.data.as_slice()));
+        let mut speech_player = self.base().get_node_as::<AudioStreamPlayer>("speech_player");
+        speech_player.set_stream(&stream);
+        speech_player.play();
+    }
+
+    fn get_speech_config(&self) -> SpeechConfig {
+        let engine = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer52/OptionButton").get_selected_id();
+        let url_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer53/LineEdit").get_text();
+        let voice_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer54/LineEdit").get_text();
+        let speed_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer55/LineEdit").get_text();
+        let dictionary_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer56/LineEdit").get_text();
+        SpeechConfig {
+            engine,
+            url: url_text.to_string().trim().to_string(),
+            voice: voice_text.to_string().trim().to_string(),
+            speed: speed_text.to_string().parse::<f32>().unwrap_or(1.0).clamp(0.5, 2.0),
+            dictionary: dictionary_text.to_string().trim().to_string(),
+        }
+    }
+
+    fn get_highlight_config(&self) -> HighlightConfig {
+        let mode = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer39/OptionButton").get_selected_id();
+        let style = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer40/OptionButton").get_selected_id();
+        let known_jlpt = self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer41/OptionButton").get_selected_id();
+        let known_frequency_text = self.base().get_node_as::<LineEdit>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer42/LineEdit").get_text();
+        HighlightConfig {
+            mode,
+            style,
+            known_jlpt,
+            known_frequency: known_frequency_text.to_string().parse::<u32>().unwrap_or(0),
+        }
+    }
+
+    fn get_pitch_style(&self) -> i32 {
+        self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer57/OptionButton").get_selected_id()
+    }
+
+    fn get_reading_format(&self) -> i32 {
+        self.base().get_node_as::<OptionButton>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/HBoxContainer37/OptionButton").get_selected_id()
+    }
+
     #[func]
     fn refresh_preview_packet(&self) {
         let mut translation_packet = self.base().get_node_as::<PanelContainer>("sandGUI/MarginContainer/VBoxContainer/vbox_content/TabContainer/Settings/ScrollContainer/VBoxContainer/translation_packet");
+        let preview_text = "これはその一例だ。";
+        let preview_packet = TranslationPacket {
+            jp_text: preview_text.to_string(),
+            jp_read: "これはそのいちれいだ。".to_string(),
+            eng_text: String::new(),
+            bounds: Vec::new(),
+            confidences: Vec::new(),
+            speaker: None,
+            words: self.tokenizer.as_ref().map(|tokenizer| tokenizer.tokenize(preview_text)).unwrap_or_default(),
+        };
+        self.render_jp_text(&translation_packet, &preview_packet);
         self.post_process_packet(&mut translation_packet);
+        translation_packet.get_node_as::<HBoxContainer>("VBoxContainer/action_bar").set_visible(false);
+        // Packets on screen follow the new settings without another capture
+        for (packet, translation_packet) in self.previous_packets.iter().zip(self.packet_nodes.iter()) {
+            let mut translation_packet = translation_packet.clone();
+            self.render_jp_text(&translation_packet, packet);
+            self.post_process_packet(&mut translation_packet);
+        }
     }
 }
@@ -0,0 +1,95 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use lindera::dictionary::load_dictionary;
+use lindera::mode::Mode;
+use lindera::segmenter::Segmenter;
+use crate::reading::{self, is_kanji};
+
+// IPADIC detail columns
+const POS: usize = 0;
+const POS_DETAIL: usize = 1;
+const CONJUGATION: usize = 4;
+const BASE_FORM: usize = 6;
+const READING: usize = 7;
+
+// One word of a packet's Japanese line
+#[derive(Clone)]
+pub struct Word {
+    pub surface: String,
+    // Character offset of the word in the line
+    pub start: usize,
+    pub pos: String,
+    pub pos_detail: String,
+    pub conjugation: String,
+    // Dictionary form, the surface itself for words that do not inflect
+    pub base_form: String,
+    // Hiragana
+    pub reading: String,
+}
+
+impl Word {
+    pub fn has_kanji(&self) -> bool {
+        self.surface.chars().any(is_kanji)
+    }
+
+    pub fn is_punctuation(&self) -> bool {
+        self.pos == "記号"
+    }
+
+    // Known words are listed by dictionary form, so every conjugation counts
+    pub fn is_known(&self, known_words: &HashSet<String>) -> bool {
+        known_words.contains(&self.base_form) || known_words.contains(&self.surface)
+    }
+}
+
+// ============================================================
+// -- Tokenizer --
+// MeCab-style morphological analysis with the IPADIC dictionary
+// built into the extension, so words are split and read with
+// the context around them and without a connection
+// ============================================================
+pub struct Tokenizer {
+    segmenter: Segmenter,
+}
+
+impl Tokenizer {
+    pub fn new() -> Result<Self, String> {
+        let dictionary = load_dictionary("embedded://ipadic").map_err(|e| format!("The IPADIC dictionary could not be loaded: {}", e))?;
+        Ok(Tokenizer { segmenter: Segmenter::new(Mode::Normal, dictionary, None) })
+    }
+
+    pub fn tokenize(&self, text: &str) -> Vec<Word> {
+        let mut tokens = match self.segmenter.segment(Cow::Borrowed(text)) {
+            Ok(tokens) => tokens,
+            Err(_) => return Vec::new(),
+        };
+        let mut words = Vec::new();
+        for token in tokens.iter_mut() {
+            let surface = token.surface.to_string();
+            let start = text[..token.byte_start].chars().count();
+            let details: Vec<String> = token.details().iter().map(|detail| detail.to_string()).collect();
+            let detail = |i: usize| details.get(i).filter(|detail| detail.as_str() != "*").cloned();
+            // Words missing from the dictionary have no reading, KAKASI guesses one
+            let reading = match detail(READING) {
+                Some(reading) => reading.chars().map(reading::to_hiragana).collect(),
+                None if surface.chars().any(is_kanji) => kakasi::convert(&surface).hiragana,
+                None => surface.chars().map(reading::to_hiragana).collect(),
+            };
+            words.push(Word {
+                start,
+                pos: detail(POS).unwrap_or_default(),
+                pos_detail: detail(POS_DETAIL).unwrap_or_default(),
+                conjugation: detail(CONJUGATION).unwrap_or_default(),
+                base_form: detail(BASE_FORM).unwrap_or_else(|| surface.clone()),
+                reading,
+                surface,
+            });
+        }
+        words
+    }
+}
+
+// The reading of a whole line, word by word
+pub fn line_reading(words: &[Word]) -> String {
+    words.iter().map(|word| word.reading.as_str()).collect()
+}
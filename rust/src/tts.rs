@@ -0,0 +1,164 @@
+use std::process::{Command, Stdio};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+// Option order in the Speech Engine list
+pub const ENGINE_VOICEVOX: i32 = 0;
+pub const ENGINE_OPEN_JTALK: i32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SpeechConfig {
+    pub engine: i32,
+    // VOICEVOX and engines built on it, like COEIROINK or SHAREVOX, listen here
+    pub url: String,
+    // Style id for VOICEVOX, path to an .htsvoice file for Open JTalk
+    pub voice: String,
+    pub speed: f32,
+    // Open JTalk's system dictionary folder
+    pub dictionary: String,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        SpeechConfig {
+            engine: ENGINE_VOICEVOX,
+            url: "http://127.0.0.1:50021".to_string(),
+            voice: "1".to_string(),
+            speed: 1.0,
+            dictionary: String::new(),
+        }
+    }
+}
+
+// PCM samples of a WAV file, laid out the way AudioStreamWAV wants them
+pub struct Wav {
+    pub sample_rate: u32,
+    pub is_stereo: bool,
+    pub is_16_bit: bool,
+    pub data: Vec<u8>,
+}
+
+// Returns the spoken text as a WAV file
+pub async fn synthesize(config: &SpeechConfig, text: &str) -> Result<Vec<u8>, String> {
+    match config.engine {
+        ENGINE_OPEN_JTALK => synthesize_open_jtalk(config, text),
+        _ => synthesize_voicevox(config, text).await,
+    }
+}
+
+// ________________________________________
+// VOICEVOX speaks in two steps: the query
+// gives accents and timing for the text,
+// which can be tweaked before synthesis
+// ----------------------------------------
+async fn synthesize_voicevox(config: &SpeechConfig, text: &str) -> Result<Vec<u8>, String> {
+    let speaker = config.voice.trim().parse::<u32>().map_err(|_| format!("The VOICEVOX voice must be a style id, like 1, not {}!", config.voice))?;
+    let speaker_id = speaker.to_string();
+    let base_url = config.url.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let mut audio_query = client.post(format!("{}/audio_query", base_url))
+        .query(&[("text", text), ("speaker", speaker_id.as_str())])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to reach the speech engine at {}. Is VOICEVOX running? {}", base_url, e))?
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("The speech engine sent an unexpected response: {}", e))?;
+    audio_query["speedScale"] = Value::from(config.speed);
+    let wav_bytes = client.post(format!("{}/synthesis", base_url))
+        .query(&[("speaker", speaker_id.as_str())])
+        .json(&audio_query)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("The speech engine could not speak the line: {}", e))?
+        .bytes()
+        .await
+        .map_err(|e| format!("The speech engine sent an unexpected response: {}", e))?;
+    Ok(wav_bytes.to_vec())
+}
+
+// Lines can be spoken while an earlier one is still being written
+static SPEECH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn synthesize_open_jtalk(config: &SpeechConfig, text: &str) -> Result<Vec<u8>, String> {
+    if config.dictionary.trim().is_empty() {
+        return Err("Set Open JTalk's dictionary folder under SPEECH first! It is the naist-jdic folder holding sys.dic.".to_string());
+    }
+    let dictionary = Path::new(config.dictionary.trim());
+    if !dictionary.is_dir() {
+        return Err(format!("Open JTalk's dictionary folder {} does not exist!", dictionary.display()));
+    }
+    let output_path = std::env::temp_dir().join(format!("vets_speech_{}_{}.wav", std::process::id(), SPEECH_COUNT.fetch_add(1, Ordering::Relaxed)));
+    let speech = run_open_jtalk(config, dictionary, text, &output_path);
+    let _ = std::fs::remove_file(&output_path);
+    speech
+}
+
+fn run_open_jtalk(config: &SpeechConfig, dictionary: &Path, text: &str, output_path: &Path) -> Result<Vec<u8>, String> {
+    let mut child = Command::new("open_jtalk")
+        .arg("-x").arg(dictionary)
+        .arg("-m").arg(&config.voice)
+        .arg("-r").arg(config.speed.to_string())
+        .arg("-ow").arg(output_path)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start open_jtalk. Is it installed and on your PATH? {}", e))?;
+    // Open JTalk reads the text to speak from stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes()).map_err(|e| format!("Failed to pass the line to open_jtalk: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("open_jtalk stopped unexpectedly: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("open_jtalk could not speak the line. Check the dictionary folder and voice file! {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    std::fs::read(output_path).map_err(|e| format!("Failed to read the speech from open_jtalk: {}", e))
+}
+
+// ________________________________________
+// Read the fmt and data chunks of a PCM WAV.
+// Godot takes 8-bit samples signed, WAV
+// stores them unsigned
+// ----------------------------------------
+pub fn parse_wav(bytes: &[u8]) -> Result<Wav, String> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err("The speech engine did not send a WAV file!".to_string());
+    }
+    let read_u16 = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let read_u32 = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let chunk_id = &bytes[i..i + 4];
+        let chunk_size = read_u32(i + 4) as usize;
+        let chunk_start = i + 8;
+        let chunk_end = (chunk_start + chunk_size).min(bytes.len());
+        match chunk_id {
+            b"fmt " if chunk_size >= 16 && chunk_start + 16 <= bytes.len() => {
+                format = Some((read_u16(chunk_start), read_u16(chunk_start + 2), read_u32(chunk_start + 4), read_u16(chunk_start + 14)));
+            },
+            b"data" => {
+                let Some((audio_format, channels, sample_rate, bits)) = format else {
+                    return Err("The WAV file has no format chunk!".to_string());
+                };
+                if audio_format != 1 || !matches!(bits, 8 | 16) || !matches!(channels, 1 | 2) {
+                    return Err(format!("Only 8 or 16-bit PCM WAV can be played, not format {} at {} bits!", audio_format, bits));
+                }
+                let mut data = bytes[chunk_start..chunk_end].to_vec();
+                if bits == 8 {
+                    data.iter_mut().for_each(|sample| *sample = sample.wrapping_sub(128));
+                }
+                return Ok(Wav { sample_rate, is_stereo: channels == 2, is_16_bit: bits == 16, data });
+            },
+            _ => {},
+        }
+        // Chunks are padded to an even size
+        i = chunk_start + chunk_size + chunk_size % 2;
+    }
+    Err("The WAV file has no audio!".to_string())
+}
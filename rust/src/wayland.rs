@@ -0,0 +1,403 @@
+// ------ WAYLAND SCREEN CAPTURE ------
+// Wayland sessions do not expose other windows to clients, so xcap finds
+// nothing. Instead the xdg-desktop-portal ScreenCast interface asks the user
+// for a window or monitor once, hands back a PipeWire stream for it, and a
+// restore token that lets later sessions skip the permission prompt.
+// The session and its stream stay open between captures: each capture reads
+// the newest frame of the stream. The portal is reached on the session bus,
+// so the tests below run against a stand-in portal on a private bus.
+use std::os::fd::OwnedFd;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use ashpd::desktop::Session;
+use ashpd::desktop::screencast::{CursorMode, PersistMode, Screencast, SourceType};
+use ashpd::WindowIdentifier;
+use image::RgbaImage;
+use pipewire as pw;
+use pw::spa;
+use pw::spa::param::video::{VideoFormat, VideoInfoRaw};
+use pw::spa::pod::Pod;
+use tokio::runtime::Runtime;
+
+const FRAME_TIMEOUT: Duration = Duration::from_secs(3);
+
+// ashpd keeps one D-Bus connection for the whole process, which lives on
+// the runtime it was made on, so every portal call goes through this one
+static PORTAL_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+pub struct PortalSession {
+    session: Session<'static>,
+    fd: OwnedFd,
+    node_id: u32,
+    restore_token: Option<String>,
+}
+
+// The newest buffer of the stream, converted to RGBA only when a capture asks for it
+struct RawFrame {
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    stride: usize,
+    is_bgr: bool,
+}
+
+pub fn is_wayland_session() -> bool {
+    std::env::var("XDG_SESSION_TYPE").map(|t| t == "wayland").unwrap_or(false)
+        || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+fn portal_runtime() -> Result<&'static Runtime, String> {
+    if let Some(runtime) = PORTAL_RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Runtime::new().map_err(|e| format!("ScreenCast portal failure: {}", e))?;
+    Ok(PORTAL_RUNTIME.get_or_init(|| runtime))
+}
+
+// ________________________________________
+// Ask the portal for a window or monitor.
+// With a restore token from an earlier
+// session the portal skips its prompt
+// ----------------------------------------
+pub async fn open_session(restore_token: Option<&str>) -> ashpd::Result<PortalSession> {
+    let proxy = Screencast::new().await?;
+    let session = proxy.create_session().await?;
+    proxy.select_sources(
+        &session,
+        CursorMode::Hidden,
+        SourceType::Window | SourceType::Monitor,
+        false,
+        restore_token,
+        PersistMode::ExplicitlyRevoked,
+    ).await?;
+    let response = proxy.start(&session, &WindowIdentifier::default()).await?.response()?;
+    let node_id = match response.streams().first() {
+        Some(stream) => stream.pipe_wire_node_id(),
+        None => return Err(ashpd::Error::NoResponse),
+    };
+    let fd = proxy.open_pipe_wire_remote(&session).await?;
+    Ok(PortalSession {
+        session,
+        fd,
+        node_id,
+        restore_token: response.restore_token().map(|token| token.to_string()),
+    })
+}
+
+// ============================================================
+// -- PortalCapture --
+// An open ScreenCast session and the thread reading its stream.
+// Dropping it stops the stream and closes the session
+// ============================================================
+pub struct PortalCapture {
+    session: Session<'static>,
+    restore_token: Option<String>,
+    latest_frame: Arc<Mutex<Option<RawFrame>>>,
+    is_stopping: Arc<AtomicBool>,
+    stream_thread: Option<JoinHandle<()>>,
+}
+
+impl PortalCapture {
+    pub fn open(restore_token: Option<String>) -> Result<PortalCapture, String> {
+        let portal_session = portal_runtime()?.block_on(open_session(restore_token.as_deref()))
+            .map_err(|e| format!("ScreenCast portal failure: {}", e))?;
+        let latest_frame = Arc::new(Mutex::new(None));
+        let is_stopping = Arc::new(AtomicBool::new(false));
+        let latest_frame_clone = Arc::clone(&latest_frame);
+        let is_stopping_clone = Arc::clone(&is_stopping);
+        let (fd, node_id) = (portal_session.fd, portal_session.node_id);
+        let stream_thread = thread::spawn(move || {
+            if let Err(error) = run_stream(fd, node_id, latest_frame_clone, is_stopping_clone) {
+                godot::global::godot_print!("{}", error);
+            }
+        });
+        Ok(PortalCapture {
+            session: portal_session.session,
+            restore_token: portal_session.restore_token,
+            latest_frame,
+            is_stopping,
+            stream_thread: Some(stream_thread),
+        })
+    }
+
+    // Restore tokens are single-use: the portal hands out a new one every session
+    pub fn restore_token(&self) -> Option<String> {
+        self.restore_token.clone()
+    }
+
+    // False once the stream stopped, like when the shared window was closed
+    pub fn is_open(&self) -> bool {
+        self.stream_thread.as_ref().is_some_and(|stream_thread| !stream_thread.is_finished())
+    }
+
+    // The newest frame, waiting for the first one right after the session opened
+    pub fn frame(&self) -> Result<RgbaImage, String> {
+        let started = Instant::now();
+        loop {
+            if let Some(raw_frame) = self.latest_frame.lock().unwrap().as_ref() {
+                return frame_to_rgba(&raw_frame.bytes, raw_frame.width, raw_frame.height, raw_frame.stride, raw_frame.is_bgr)
+                    .ok_or_else(|| format!("The ScreenCast stream sent a frame that could not be read!"));
+            }
+            if !self.is_open() {
+                return Err(format!("The ScreenCast stream stopped!"));
+            }
+            if started.elapsed() > FRAME_TIMEOUT {
+                return Err(format!("No frame received from the ScreenCast stream!"));
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+impl Drop for PortalCapture {
+    fn drop(&mut self) {
+        self.is_stopping.store(true, Ordering::Relaxed);
+        if let Some(stream_thread) = self.stream_thread.take() {
+            let _ = stream_thread.join();
+        }
+        if let Ok(runtime) = portal_runtime() {
+            let _ = runtime.block_on(self.session.close());
+        }
+    }
+}
+
+// ________________________________________
+// Keep the newest buffer of the stream
+// until the capture is dropped or the
+// stream ends
+// ----------------------------------------
+fn run_stream(fd: OwnedFd, node_id: u32, latest_frame: Arc<Mutex<Option<RawFrame>>>, is_stopping: Arc<AtomicBool>) -> Result<(), String> {
+    pw::init();
+    let main_loop = pw::main_loop::MainLoop::new(None).map_err(|e| format!("PipeWire failure: {}", e))?;
+    let context = pw::context::Context::new(&main_loop).map_err(|e| format!("PipeWire failure: {}", e))?;
+    let core = context.connect_fd(fd, None).map_err(|e| format!("PipeWire failure: {}", e))?;
+    let stream = pw::stream::Stream::new(
+        &core,
+        "vets-capture",
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+        },
+    ).map_err(|e| format!("PipeWire failure: {}", e))?;
+
+    let is_stopping_clone = Arc::clone(&is_stopping);
+    let _listener = stream
+        .add_local_listener_with_user_data(VideoInfoRaw::default())
+        .state_changed(move |_, _, _, state| {
+            if matches!(state, pw::stream::StreamState::Error(_) | pw::stream::StreamState::Unconnected) {
+                is_stopping_clone.store(true, Ordering::Relaxed);
+            }
+        })
+        .param_changed(|_, format, id, param| {
+            if let Some(param) = param {
+                if id == spa::param::ParamType::Format.as_raw() {
+                    let _ = format.parse(param);
+                }
+            }
+        })
+        .process(move |stream, format| {
+            if let Some(mut buffer) = stream.dequeue_buffer() {
+                let datas = buffer.datas_mut();
+                if datas.is_empty() {
+                    return;
+                }
+                let size = format.size();
+                let stride = datas[0].chunk().stride() as usize;
+                let offset = datas[0].chunk().offset() as usize;
+                let is_bgr = matches!(format.format(), VideoFormat::BGRx | VideoFormat::BGRA);
+                if let Some(bytes) = datas[0].data() {
+                    let mut latest_frame = latest_frame.lock().unwrap();
+                    // The buffer is reused, so a new frame costs one copy
+                    let mut frame_bytes = latest_frame.take().map(|raw_frame| raw_frame.bytes).unwrap_or_default();
+                    frame_bytes.clear();
+                    frame_bytes.extend_from_slice(&bytes[offset.min(bytes.len())..]);
+                    *latest_frame = Some(RawFrame { bytes: frame_bytes, width: size.width, height: size.height, stride, is_bgr });
+                }
+            }
+        })
+        .register()
+        .map_err(|e| format!("PipeWire failure: {}", e))?;
+
+    let format_pod = enum_format_pod()?;
+    let mut params = [Pod::from_bytes(&format_pod).ok_or_else(|| format!("PipeWire failure: invalid format"))?];
+    stream.connect(
+        spa::utils::Direction::Input,
+        Some(node_id),
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    ).map_err(|e| format!("PipeWire failure: {}", e))?;
+
+    while !is_stopping.load(Ordering::Relaxed) {
+        main_loop.loop_().iterate(Duration::from_millis(50));
+    }
+    let _ = stream.disconnect();
+    Ok(())
+}
+
+// Only packed 8-bit RGB formats are requested, so every pixel is 4 bytes
+fn enum_format_pod() -> Result<Vec<u8>, String> {
+    let object = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        spa::param::ParamType::EnumFormat,
+        spa::pod::property!(spa::param::format::FormatProperties::MediaType, Id, spa::param::format::MediaType::Video),
+        spa::pod::property!(spa::param::format::FormatProperties::MediaSubtype, Id, spa::param::format::MediaSubtype::Raw),
+        spa::pod::property!(
+            spa::param::format::FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            VideoFormat::BGRx,
+            VideoFormat::BGRx,
+            VideoFormat::BGRA,
+            VideoFormat::RGBx,
+            VideoFormat::RGBA
+        ),
+    );
+    spa::pod::serialize::PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &spa::pod::Value::Object(object))
+        .map(|(cursor, _)| cursor.into_inner())
+        .map_err(|e| format!("PipeWire failure: {:?}", e))
+}
+
+fn frame_to_rgba(bytes: &[u8], width: u32, height: u32, stride: usize, is_bgr: bool) -> Option<RgbaImage> {
+    let row_length = width as usize * 4;
+    let stride = if stride == 0 { row_length } else { stride };
+    if width == 0 || height == 0 || bytes.len() < stride * (height as usize - 1) + row_length {
+        return None;
+    }
+    let mut rgba = Vec::with_capacity(row_length * height as usize);
+    for row in 0..height as usize {
+        for pixel in bytes[row * stride..row * stride + row_length].chunks_exact(4) {
+            if is_bgr {
+                rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+            } else {
+                rgba.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+    }
+    RgbaImage::from_raw(width, height, rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+    use ashpd::zbus::blocking::{connection, Connection, MessageIterator};
+    use ashpd::zbus::message::Type as MessageType;
+    use ashpd::zbus::zvariant::{self, OwnedObjectPath, OwnedValue, Value};
+
+    const NODE_ID: u32 = 42;
+
+    // What the application asked of the stand-in portal
+    #[derive(Default)]
+    struct PortalCalls {
+        restore_tokens: Vec<Option<String>>,
+        closed_sessions: Vec<String>,
+    }
+
+    // Object path the portal uses for a request or session of this client
+    fn handle_path(kind: &str, sender: &str, options: &HashMap<String, OwnedValue>, token_key: &str) -> String {
+        let token: String = options.get(token_key).unwrap().try_clone().unwrap().try_into().unwrap();
+        format!("/org/freedesktop/portal/desktop/{}/{}/{}", kind, sender.trim_start_matches(':').replace('.', "_"), token)
+    }
+
+    // ________________________________________
+    // Answer ScreenCast calls like
+    // xdg-desktop-portal does once the user
+    // picked a window
+    // ----------------------------------------
+    fn run_portal(portal: Connection, calls: Arc<Mutex<PortalCalls>>) {
+        let mut session_count = 0;
+        for message in MessageIterator::from(&portal) {
+            let message = message.unwrap();
+            let header = message.header();
+            if header.message_type() != MessageType::MethodCall {
+                continue;
+            }
+            let sender = header.sender().unwrap().to_string();
+            let (Some(interface), Some(member)) = (header.interface(), header.member()) else { continue };
+            let (interface, member) = (interface.to_string(), member.to_string());
+            let (options, results): (HashMap<String, OwnedValue>, HashMap<&str, Value>) = match (interface.as_str(), member.as_str()) {
+                ("org.freedesktop.portal.ScreenCast", "CreateSession") => {
+                    let options: HashMap<String, OwnedValue> = message.body().deserialize().unwrap();
+                    let session_handle = handle_path("session", &sender, &options, "session_handle_token");
+                    (options, HashMap::from([("session_handle", Value::from(session_handle))]))
+                },
+                ("org.freedesktop.portal.ScreenCast", "SelectSources") => {
+                    let (_, options): (OwnedObjectPath, HashMap<String, OwnedValue>) = message.body().deserialize().unwrap();
+                    let restore_token = options.get("restore_token").map(|token| token.try_clone().unwrap().try_into().unwrap());
+                    calls.lock().unwrap().restore_tokens.push(restore_token);
+                    (options, HashMap::new())
+                },
+                ("org.freedesktop.portal.ScreenCast", "Start") => {
+                    let (_, _, options): (OwnedObjectPath, String, HashMap<String, OwnedValue>) = message.body().deserialize().unwrap();
+                    session_count += 1;
+                    let streams = vec![(NODE_ID, HashMap::<String, Value>::new())];
+                    (options, HashMap::from([
+                        ("streams", Value::from(streams)),
+                        ("restore_token", Value::from(format!("token-{}", session_count))),
+                    ]))
+                },
+                ("org.freedesktop.portal.ScreenCast", "OpenPipeWireRemote") => {
+                    let file = std::fs::File::open("/dev/null").unwrap();
+                    portal.reply(&message, &zvariant::Fd::from(&file)).unwrap();
+                    continue;
+                },
+                ("org.freedesktop.portal.Session", "Close") => {
+                    calls.lock().unwrap().closed_sessions.push(header.path().unwrap().to_string());
+                    portal.reply(&message, &()).unwrap();
+                    continue;
+                },
+                _ => {
+                    portal.reply_error(&message, "org.freedesktop.DBus.Error.UnknownMethod", &"Not part of the stand-in portal").unwrap();
+                    continue;
+                },
+            };
+            let request_path = handle_path("request", &sender, &options, "handle_token");
+            portal.reply(&message, &OwnedObjectPath::try_from(request_path.clone()).unwrap()).unwrap();
+            portal.emit_signal(Some(sender.as_str()), request_path.as_str(), "org.freedesktop.portal.Request", "Response", &(0u32, results)).unwrap();
+        }
+    }
+
+    // ashpd connects to the session bus once per process, so every portal
+    // check shares this one test and its private bus
+    #[test]
+    fn portal_session_restores_and_closes() {
+        let Ok(mut bus) = Command::new("dbus-daemon").args(["--session", "--nofork", "--print-address"]).stdout(Stdio::piped()).spawn() else {
+            eprintln!("dbus-daemon not installed, skipping the stand-in portal test");
+            return;
+        };
+        let mut address = String::new();
+        BufReader::new(bus.stdout.take().unwrap()).read_line(&mut address).unwrap();
+        let address = address.trim().to_string();
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let portal = connection::Builder::address(address.as_str()).unwrap()
+            .name("org.freedesktop.portal.Desktop").unwrap()
+            .build().unwrap();
+        let calls = Arc::new(Mutex::new(PortalCalls::default()));
+        let calls_clone = Arc::clone(&calls);
+        thread::spawn(move || run_portal(portal, calls_clone));
+
+        let runtime = portal_runtime().unwrap();
+        let first = runtime.block_on(open_session(None)).unwrap();
+        assert_eq!(first.node_id, NODE_ID);
+        assert_eq!(first.restore_token.as_deref(), Some("token-1"));
+        // The next session skips the prompt with the token of the last one
+        let second = runtime.block_on(open_session(first.restore_token.as_deref())).unwrap();
+        assert_eq!(second.restore_token.as_deref(), Some("token-2"));
+        runtime.block_on(first.session.close()).unwrap();
+        runtime.block_on(second.session.close()).unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.restore_tokens, vec![None, Some("token-1".to_string())]);
+        assert_eq!(calls.closed_sessions.len(), 2);
+        assert_ne!(calls.closed_sessions[0], calls.closed_sessions[1]);
+        drop(calls);
+        let _ = bus.kill();
+    }
+}